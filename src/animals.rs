@@ -1,6 +1,10 @@
-use crate::game;
+use crate::minimap;
+use crate::navigation::NavGrid;
 
 use crate::game::GameLayer;
+use crate::rng::{GameRng, RngStream};
+use crate::vehicles;
+use bevy::ecs::system::SystemState;
 use bevy::prelude::*;
 use heron::prelude::*;
 use rand::distributions::{Distribution, Uniform};
@@ -9,71 +13,103 @@ use rand::distributions::{Distribution, Uniform};
 pub struct Animal {
     pub move_speed: f32,
     pub move_frequency: f32,
-    pub jump_height: f32,
     pub direction_change_chance: f32,
     pub time_since_move: f32,
+    pub flee_distance: f32,
+    pub flee_speed: f32,
+    // sound asset, or empty for a quiet one
+    pub call: &'static str,
 }
 
-const sheep: Animal = Animal {
+// anything with this on it scares the animals away
+#[derive(Component)]
+pub struct Herder {}
+
+// far enough from the combine that they don't land on it
+const CONSOLE_SPAWN_DISTANCE: f32 = 8.;
+
+const SHEEP: Animal = Animal {
     move_speed: 3.0,
     move_frequency: 10.0,
-    jump_height: 0.5,
     direction_change_chance: 0.5,
     time_since_move: 0.,
+    flee_distance: 12.0,
+    flee_speed: 6.0,
+    call: "sounds/sheep.wav",
 };
 
-const pig: Animal = Animal {
+const PIG: Animal = Animal {
     move_speed: 2.0,
     move_frequency: 5.0,
-    jump_height: 0.25,
     direction_change_chance: 0.2,
     time_since_move: 0.,
+    flee_distance: 8.0,
+    flee_speed: 4.0,
+    call: "sounds/pig.wav",
 };
 
-pub fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub enum AnimalKind {
+    Sheep,
+    Pig,
+}
+
+pub const ANIMAL_KINDS: [AnimalKind; 2] = [AnimalKind::Sheep, AnimalKind::Pig];
+
+impl AnimalKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimalKind::Sheep => "sheep",
+            AnimalKind::Pig => "pig",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AnimalKind> {
+        ANIMAL_KINDS.into_iter().find(|kind| kind.name() == name)
+    }
+
+    fn animal(&self) -> Animal {
+        match self {
+            AnimalKind::Sheep => SHEEP,
+            AnimalKind::Pig => PIG,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            AnimalKind::Sheep => Color::rgba(0.8, 0.8, 0.8, 1.),
+            AnimalKind::Pig => Color::rgba(0.8, 0.5, 0.5, 1.),
+        }
+    }
+
+    fn icon_color(&self) -> Color {
+        match self {
+            AnimalKind::Sheep => Color::WHITE,
+            AnimalKind::Pig => Color::PINK,
+        }
+    }
+}
+
+pub fn spawn_animal(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    kind: AnimalKind,
+    transform: Transform,
 ) {
-    let sheep_id = commands
+    commands
         .spawn_bundle(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Cube { size: 1. })),
-            material: materials.add(Color::rgba(0.8, 0.8, 0.8, 1.).into()),
-            transform: Transform {
-                translation: Vec3::new(5., 0.1, 0.).into(),
-                ..default()
-            },
+            material: materials.add(kind.color().into()),
+            transform,
             ..default()
         })
-        .insert(sheep)
-        .insert(
-            CollisionLayers::none()
-                .with_groups(&[GameLayer::Animal, GameLayer::Obstacle])
-                .with_masks(&[GameLayer::World, GameLayer::Obstacle, GameLayer::Vehicle]),
-        )
-        .insert(RigidBody::Dynamic)
-        .insert(Velocity::from_linear(Vec3::ZERO).with_angular(AxisAngle::new(Vec3::Y, 0.)))
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3 {
-                x: 1.0,
-                y: 1.0,
-                z: 1.0,
-            },
-            border_radius: Some(0.2),
-        });
-
-    let sheep_id = commands
-        .spawn_bundle(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Cube { size: 1. })),
-            material: materials.add(Color::rgba(0.8, 0.5, 0.5, 1.).into()),
-            transform: Transform {
-                translation: Vec3::new(-5., 0.1, 0.).into(),
-                ..default()
-            },
-            ..default()
+        .insert(kind.animal())
+        .insert(kind)
+        .insert(minimap::MinimapIcon {
+            color: kind.icon_color(),
+            size: 4.,
         })
-        .insert(pig)
         .insert(
             CollisionLayers::none()
                 .with_groups(&[GameLayer::Animal, GameLayer::Obstacle])
@@ -91,21 +127,115 @@ pub fn setup(
         });
 }
 
+pub fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    spawn_animal(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        AnimalKind::Sheep,
+        Transform::from_xyz(5., 0.1, 0.),
+    );
+    spawn_animal(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        AnimalKind::Pig,
+        Transform::from_xyz(-5., 0.1, 0.),
+    );
+}
+
+// what the console needs from the world to spawn animals
+type SpawnAccess<'w, 's> = (
+    Commands<'w, 's>,
+    ResMut<'w, Assets<Mesh>>,
+    ResMut<'w, Assets<StandardMaterial>>,
+);
+
+// console: `spawn sheep 3` lets three sheep loose in a ring around the combine
+pub fn console_spawn(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let (kind, count) = match args {
+        [kind] => (*kind, 1),
+        [kind, count] => (
+            *kind,
+            count
+                .parse::<usize>()
+                .map_err(|_| "the count must be a whole number".to_string())?,
+        ),
+        _ => return Err("spawn needs sheep or pig, and optionally how many".to_string()),
+    };
+    let kind = AnimalKind::from_name(kind).ok_or_else(|| format!("{} isn't sheep or pig", kind))?;
+    let centre = world
+        .query_filtered::<&Transform, With<vehicles::Combine>>()
+        .iter(world)
+        .next()
+        .map_or(Vec3::ZERO, |transform| transform.translation);
+
+    let mut state: SystemState<SpawnAccess> = SystemState::new(world);
+    let (mut commands, mut meshes, mut materials) = state.get_mut(world);
+    for index in 0..count {
+        let angle = index as f32 * std::f32::consts::TAU / count as f32;
+        let offset = Vec3::new(angle.cos(), 0., angle.sin()) * CONSOLE_SPAWN_DISTANCE;
+        spawn_animal(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            kind,
+            Transform::from_translation(Vec3::new(centre.x, 0.1, centre.z) + offset),
+        );
+    }
+    state.apply(world);
+    Ok(format!("spawned {} {}", count, kind.name()))
+}
+
 pub fn move_animals(
     mut query: Query<(&mut Animal, &mut Transform, &mut Velocity)>,
+    herders: Query<&Transform, (With<Herder>, Without<Animal>)>,
+    nav_grid: Res<NavGrid>,
+    mut rng: ResMut<GameRng>,
     time: Res<Time>,
 ) {
     for (mut animal, mut animal_transform, mut animal_velocity) in &mut query {
+        let position = animal_transform.translation;
+        let mut nearest_herder: Option<Vec3> = None;
+        for herder_transform in herders.iter() {
+            let distance = herder_transform.translation.distance(position);
+            if distance < animal.flee_distance
+                && nearest_herder.is_none_or(|nearest| distance < nearest.distance(position))
+            {
+                nearest_herder = Some(herder_transform.translation);
+            }
+        }
+
+        if let Some(herder_position) = nearest_herder {
+            let mut away = position - herder_position;
+            away.y = 0.;
+            if away.length() > 0. {
+                // cornered against a fence, they run along it instead
+                let away = nav_grid.clear_direction(position, away.normalize());
+                animal_transform.look_at(position + away, Vec3::Y);
+                animal_velocity.linear.x = animal.flee_speed * away.x;
+                animal_velocity.linear.z = animal.flee_speed * away.z;
+            }
+            animal.time_since_move = 0.;
+            continue;
+        }
+
         if animal.time_since_move > 10. / animal.move_frequency {
             let random_generator = Uniform::new(0, 359);
-            let mut rng = rand::thread_rng();
-            if random_generator.sample(&mut rng) as f32 > animal.direction_change_chance {
-                let choice = random_generator.sample(&mut rng) as f32;
+            let rng = rng.stream(RngStream::Animals);
+            if random_generator.sample(rng) as f32 > animal.direction_change_chance {
+                let choice = random_generator.sample(rng) as f32;
                 animal_transform.rotate_y(choice);
             }
+            let heading = nav_grid.clear_direction(position, animal_transform.forward());
+            animal_transform.look_at(position + heading, Vec3::Y);
             animal.time_since_move = 0.;
-            animal_velocity.linear.x = animal.move_speed * animal_transform.forward().x;
-            animal_velocity.linear.z = animal.move_speed * animal_transform.forward().z;
+            animal_velocity.linear.x = animal.move_speed * heading.x;
+            animal_velocity.linear.z = animal.move_speed * heading.z;
         }
         animal.time_since_move += time.delta_seconds();
     }
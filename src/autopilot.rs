@@ -0,0 +1,453 @@
+use crate::crop_grid::{CropCutter, CropGrid};
+use crate::drop_zone;
+use crate::game;
+use crate::header;
+use crate::header::Header;
+use crate::navigation;
+use crate::navigation::{NavGrid, NavRoute};
+use crate::vehicles;
+use crate::vehicles::{Combine, CombineStorage, Truck, TruckStorage};
+use crate::weather::Weather;
+use std::f32::consts::FRAC_PI_2;
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use heron::prelude::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TruckTask {
+    Idle,
+    Fetching,
+    Loading,
+    Delivering,
+}
+
+pub struct TruckAutopilot {
+    pub enabled: bool,
+    pub task: TruckTask,
+    replan_timer: f32,
+}
+
+#[derive(Default)]
+pub struct CombineAutopilot {
+    pub enabled: bool,
+    pub field: Option<usize>,
+}
+
+impl Default for TruckAutopilot {
+    fn default() -> Self {
+        TruckAutopilot {
+            enabled: false,
+            task: TruckTask::Idle,
+            replan_timer: 0.,
+        }
+    }
+}
+
+pub fn setup(
+    mut truck_autopilot: ResMut<TruckAutopilot>,
+    mut combine_autopilot: ResMut<CombineAutopilot>,
+) {
+    *truck_autopilot = TruckAutopilot::default();
+    *combine_autopilot = CombineAutopilot::default();
+}
+
+// fractions of capacity
+const FETCH_THRESHOLD: f32 = 0.6;
+const DELIVER_THRESHOLD: f32 = 0.9;
+
+const ALONGSIDE_OFFSET: f32 = 5.;
+const WAYPOINT_RADIUS: f32 = 3.;
+const REPLAN_INTERVAL: f32 = 1.;
+
+// lanes overlap a little so nothing gets missed between them
+const LANE_OVERLAP: f32 = 0.9;
+const LANE_WAYPOINT_RADIUS: f32 = 1.5;
+const GATE_APPROACH: f32 = 6.;
+
+pub fn toggle_truck_autopilot(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut autopilot: ResMut<TruckAutopilot>,
+    mut routes: Query<&mut NavRoute, With<Truck>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        autopilot.enabled = !autopilot.enabled;
+        autopilot.task = TruckTask::Idle;
+        for mut route in routes.iter_mut() {
+            route.waypoints.clear();
+        }
+    }
+}
+
+pub fn toggle_combine_autopilot(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut autopilot: ResMut<CombineAutopilot>,
+    mut routes: Query<&mut NavRoute, With<Combine>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::C) {
+        autopilot.enabled = !autopilot.enabled;
+        autopilot.field = None;
+        for mut route in routes.iter_mut() {
+            route.waypoints.clear();
+        }
+    }
+}
+
+// update_vehicle reverses towards anything behind it, which is no good for long trips,
+// so swing round instead
+fn steer_towards(transform: &Transform, target: Vec3) -> Vec2 {
+    let to_target = Vec2::new(
+        target.x - transform.translation.x,
+        target.z - transform.translation.z,
+    );
+    if to_target.length() < 0.01 {
+        return Vec2::ZERO;
+    }
+    let forward = Vec2::new(transform.forward().x, transform.forward().z);
+    let angle = to_target.angle_between(forward);
+    if angle.abs() > FRAC_PI_2 {
+        return Vec2::from_angle(-angle.signum() * FRAC_PI_2).rotate(forward);
+    }
+    to_target.normalize()
+}
+
+type DrivenTrucks<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Truck,
+        &'static Transform,
+        &'static mut Velocity,
+        &'static mut NavRoute,
+    ),
+    Without<Combine>,
+>;
+
+type DropZones<'w, 's> = Query<
+    'w,
+    's,
+    (&'static drop_zone::DropZone, &'static Transform),
+    (Without<Truck>, Without<Combine>),
+>;
+
+// how full the two vehicles are, which is what the truck picks its next job by
+#[derive(SystemParam)]
+pub struct Hoppers<'w, 's> {
+    combine: Res<'w, CombineStorage>,
+    truck: Res<'w, TruckStorage>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+pub fn drive_truck(
+    mut autopilot: ResMut<TruckAutopilot>,
+    nav_grid: Res<NavGrid>,
+    mut trucks: DrivenTrucks,
+    combines: Query<&Transform, With<Combine>>,
+    drop_zones: DropZones,
+    hoppers: Hoppers,
+    time: Res<Time>,
+) {
+    if !autopilot.enabled {
+        return;
+    }
+    let Hoppers {
+        combine: combine_store,
+        truck: truck_store,
+        ..
+    } = hoppers;
+    if let Ok((mut truck, transform, mut velocity, mut route)) = trucks.get_single_mut() {
+        let truck_position = transform.translation;
+        let truck_full = truck_store.contents.total() >= truck_store.capacity;
+        let alongside = combines
+            .get_single()
+            .ok()
+            .map(|combine| combine.translation + combine.right() * ALONGSIDE_OFFSET);
+
+        let task = autopilot.task;
+        let mut next_task = task;
+        let mut target = None;
+        match task {
+            TruckTask::Idle => {
+                if truck_store.contents.total() as f32
+                    >= DELIVER_THRESHOLD * truck_store.capacity as f32
+                {
+                    next_task = TruckTask::Delivering;
+                } else if combine_store.contents.total() as f32
+                    >= FETCH_THRESHOLD * combine_store.capacity as f32
+                {
+                    next_task = TruckTask::Fetching;
+                }
+            }
+            TruckTask::Fetching => {
+                if truck_full {
+                    next_task = TruckTask::Delivering;
+                } else if let Some(alongside) = alongside {
+                    if alongside.distance(truck_position) < WAYPOINT_RADIUS {
+                        next_task = TruckTask::Loading;
+                    } else {
+                        target = Some(alongside);
+                    }
+                }
+            }
+            TruckTask::Loading => {
+                // transfer_harvest does the actual work while we sit here
+                let in_range = alongside.is_some_and(|alongside| {
+                    alongside.distance(truck_position) < vehicles::TRANSFER_DISTANCE
+                });
+                if !in_range {
+                    next_task = TruckTask::Fetching;
+                } else if truck_full || combine_store.contents.total() == 0 {
+                    if truck_store.contents.total() as f32
+                        >= DELIVER_THRESHOLD * truck_store.capacity as f32
+                    {
+                        next_task = TruckTask::Delivering;
+                    } else {
+                        next_task = TruckTask::Idle;
+                    }
+                }
+            }
+            TruckTask::Delivering => {
+                if let Ok((drop_zone, zone_transform)) = drop_zones.get_single() {
+                    // stop once inside, and drop_zone_accept tips the load out
+                    if drop_zone.truck_in_zone {
+                        if truck_store.contents.total() == 0 {
+                            next_task = TruckTask::Idle;
+                        }
+                    } else {
+                        target = Some(zone_transform.translation);
+                    }
+                }
+            }
+        }
+
+        if next_task != task {
+            autopilot.task = next_task;
+            route.waypoints.clear();
+        }
+
+        let mut requested_direction = Vec2::ZERO;
+        if let Some(target) = target {
+            autopilot.replan_timer -= time.delta_seconds();
+            if route.waypoints.is_empty() || autopilot.replan_timer <= 0. {
+                route.waypoints = nav_grid
+                    .find_path(
+                        truck_position,
+                        transform.forward(),
+                        target,
+                        truck.vehicle.turning_radius(),
+                    )
+                    .unwrap_or_else(|| vec![target]);
+                autopilot.replan_timer = REPLAN_INTERVAL;
+            }
+            if let Some(waypoint) =
+                navigation::advance_route(&mut route, truck_position, WAYPOINT_RADIUS)
+            {
+                requested_direction = steer_towards(transform, waypoint);
+            }
+        }
+
+        vehicles::update_vehicle(
+            &mut truck.vehicle,
+            requested_direction,
+            transform,
+            &mut velocity,
+            &time,
+        );
+    }
+}
+
+// boustrophedon lanes over whatever's left standing, starting at the gate end of each lane
+fn plan_coverage(field: &game::Field, crops: &[Vec3], from: Vec3, lane_spacing: f32) -> Vec<Vec3> {
+    let (along_axis, across_axis, half_across) = match field.entrance {
+        game::CompassPoint::North | game::CompassPoint::South => {
+            (Vec3::Z, Vec3::X, field.half_size.x)
+        }
+        game::CompassPoint::East | game::CompassPoint::West => {
+            (Vec3::X, Vec3::Z, field.half_size.y)
+        }
+    };
+    let gate_end = field.gate_position(0.).dot(along_axis);
+    let centre = Vec3::new(field.position.x, 0., field.position.y).dot(across_axis);
+    let first_lane = centre - half_across;
+    let lane_count = (2. * half_across / lane_spacing).ceil() as usize;
+
+    let mut lanes = Vec::new();
+    for lane in 0..lane_count {
+        let lane_centre = first_lane + (lane as f32 + 0.5) * lane_spacing;
+        let mut near = f32::MAX;
+        let mut far = f32::MAX;
+        for crop in crops.iter() {
+            if (crop.dot(across_axis) - lane_centre).abs() > lane_spacing / 2. {
+                continue;
+            }
+            let along = crop.dot(along_axis);
+            if near == f32::MAX || (along - gate_end).abs() < (near - gate_end).abs() {
+                near = along;
+            }
+            if far == f32::MAX || (along - gate_end).abs() > (far - gate_end).abs() {
+                far = along;
+            }
+        }
+        if near != f32::MAX {
+            lanes.push((lane_centre, near, far));
+        }
+    }
+    if from.dot(across_axis) > centre {
+        lanes.reverse();
+    }
+
+    // every other lane on the way out and the rest on the way back, so the headland turns
+    // aren't tighter than the combine can manage
+    let mut order: Vec<_> = lanes.iter().step_by(2).collect();
+    order.extend(lanes.iter().skip(1).step_by(2).rev());
+
+    let mut waypoints = Vec::new();
+    let mut from_gate_end = true;
+    for (lane_centre, near, far) in order {
+        let (start, end) = if from_gate_end {
+            (*near, *far)
+        } else {
+            (*far, *near)
+        };
+        waypoints.push(across_axis * *lane_centre + along_axis * start);
+        waypoints.push(across_axis * *lane_centre + along_axis * end);
+        from_gate_end = !from_gate_end;
+    }
+    waypoints
+}
+
+type DrivenCombines<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Combine,
+        &'static Transform,
+        &'static mut Velocity,
+        &'static mut NavRoute,
+    ),
+>;
+
+type CombineHeaders<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Header,
+        &'static mut CropCutter,
+        &'static mut Transform,
+    ),
+    Without<Combine>,
+>;
+
+// what the combine needs to know to pick a field and plan its way round it
+#[derive(SystemParam)]
+pub struct FieldSurvey<'w, 's> {
+    game: Res<'w, game::Game>,
+    nav_grid: Res<'w, NavGrid>,
+    grids: Query<'w, 's, &'static CropGrid>,
+}
+
+pub fn drive_combine(
+    mut autopilot: ResMut<CombineAutopilot>,
+    survey: FieldSurvey,
+    mut combines: DrivenCombines,
+    mut headers: CombineHeaders,
+    combine_store: Res<CombineStorage>,
+    weather: Res<Weather>,
+    time: Res<Time>,
+) {
+    if !autopilot.enabled {
+        return;
+    }
+    let FieldSurvey {
+        game,
+        nav_grid,
+        grids,
+    } = survey;
+    if let Ok((entity, mut combine, transform, mut velocity, mut route)) = combines.get_single_mut()
+    {
+        let position = transform.translation;
+        let mut requested_direction = Vec2::ZERO;
+
+        // keep the header up until we're properly into the field, so a wide one clears the gate
+        let in_crops = autopilot.field.is_some_and(|index| {
+            let field = &game.fields[index];
+            (position.x - field.position.x).abs() <= field.half_size.x
+                && (position.z - field.position.y).abs() <= field.half_size.y
+        });
+        let mut cutting_width = 0.;
+        for (mut header, mut cutter, mut header_transform) in headers.iter_mut() {
+            if header.combine == entity {
+                cutting_width = header.kind.cutting_width();
+                if header.raised == in_crops {
+                    header::set_raised(&mut header, &mut cutter, &mut header_transform, !in_crops);
+                }
+            }
+        }
+
+        // sit and wait for the truck once the hopper is full, or for the crop to dry out
+        if combine_store.contents.total() < combine_store.capacity && !weather.crop_wet() {
+            if route.waypoints.is_empty() {
+                let crops_in =
+                    |field: &game::Field| grids.get(field.crops).map_or(0, |grid| grid.standing);
+
+                // finish the field we're in before moving on to the fullest one
+                let current = autopilot
+                    .field
+                    .filter(|index| crops_in(&game.fields[*index]) > 0);
+                autopilot.field = current.or_else(|| {
+                    (0..game.fields.len())
+                        .filter(|index| crops_in(&game.fields[*index]) > 0)
+                        .max_by_key(|index| crops_in(&game.fields[*index]))
+                });
+
+                if let Some(index) = autopilot.field {
+                    let field = &game.fields[index];
+                    let mut waypoints = Vec::new();
+                    if !field.contains(position) {
+                        let gate = field.gate_position(GATE_APPROACH);
+                        waypoints = nav_grid
+                            .find_path(
+                                position,
+                                transform.forward(),
+                                gate,
+                                combine.vehicle.turning_radius(),
+                            )
+                            .unwrap_or_else(|| vec![gate]);
+                        waypoints.push(field.gate_position(-GATE_APPROACH));
+                    }
+                    let in_field = grids
+                        .get(field.crops)
+                        .map(|grid| grid.standing_positions())
+                        .unwrap_or_default();
+                    waypoints.extend(plan_coverage(
+                        field,
+                        &in_field,
+                        position,
+                        (cutting_width * LANE_OVERLAP).max(1.),
+                    ));
+                    route.waypoints = waypoints;
+                } else {
+                    // nothing left to cut
+                    autopilot.enabled = false;
+                }
+            }
+
+            if let Some(waypoint) =
+                navigation::advance_route(&mut route, position, LANE_WAYPOINT_RADIUS)
+            {
+                requested_direction = steer_towards(transform, waypoint);
+            }
+        }
+
+        vehicles::update_vehicle(
+            &mut combine.vehicle,
+            requested_direction,
+            transform,
+            &mut velocity,
+            &time,
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::crop_grid::{CropGrid, PlantState};
+use crate::game;
+use crate::game::Level;
+use crate::shop::{Upgrade, Upgrades, UPGRADES};
+
+use bevy::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_FILE: &str = "career.sav";
+const SAVE_VERSION: u32 = 1;
+// fields harvested this far get resown overnight
+const RESOW_THRESHOLD: f32 = 0.9;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EndOfDay;
+
+pub struct Career {
+    pub day: u32,
+    pub money: i32,
+    pub upgrades: Upgrades,
+    // per field, what was left standing at the end of the last day, run-length encoded.
+    // Empty means freshly sown.
+    fields: Vec<String>,
+}
+
+impl Default for Career {
+    fn default() -> Self {
+        Career {
+            day: 1,
+            money: 0,
+            upgrades: Upgrades::default(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+impl Career {
+    // picks up where the last session left off, or starts afresh
+    pub fn load() -> Career {
+        if let Some(contents) = read_save() {
+            match Career::from_save_string(&contents) {
+                Some(career) => return career,
+                None => eprintln!("couldn't read the saved career, starting a new one"),
+            }
+        }
+        Career::default()
+    }
+
+    pub fn save(&self) {
+        write_save(&self.to_save_string());
+    }
+
+    fn to_save_string(&self) -> String {
+        let mut lines = vec![
+            format!("version {}", SAVE_VERSION),
+            format!("day {}", self.day),
+            format!("money {}", self.money),
+        ];
+        for upgrade in UPGRADES {
+            lines.push(format!(
+                "upgrade {} {}",
+                upgrade.info().key,
+                self.upgrades.level(upgrade)
+            ));
+        }
+        for (index, plants) in self.fields.iter().enumerate() {
+            lines.push(format!("field {} {}", index, plants));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn from_save_string(contents: &str) -> Option<Career> {
+        let mut career = Career::default();
+        let mut version = None;
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("version") => version = words.next()?.parse::<u32>().ok(),
+                Some("day") => career.day = words.next()?.parse().ok()?,
+                Some("money") => career.money = words.next()?.parse().ok()?,
+                Some("upgrade") => {
+                    // one we've since dropped from the shop is ignored
+                    if let Some(upgrade) = Upgrade::from_key(words.next()?) {
+                        career
+                            .upgrades
+                            .set_level(upgrade, words.next()?.parse().ok()?);
+                    }
+                }
+                Some("field") => {
+                    let index: usize = words.next()?.parse().ok()?;
+                    if career.fields.len() <= index {
+                        career.fields.resize(index + 1, String::new());
+                    }
+                    career.fields[index] = words.next().unwrap_or("").to_string();
+                }
+                _ => {}
+            }
+        }
+        if version != Some(SAVE_VERSION) {
+            return None;
+        }
+        Some(career)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_save() -> Option<String> {
+    std::fs::read_to_string(SAVE_FILE).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_save(contents: &str) {
+    if let Err(error) = std::fs::write(SAVE_FILE, contents) {
+        eprintln!("couldn't save the career: {}", error);
+    }
+}
+
+// there's nowhere to put it on the web build, so the career only lasts the session there
+#[cfg(target_arch = "wasm32")]
+fn read_save() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_save(_contents: &str) {}
+
+fn state_code(state: PlantState) -> char {
+    match state {
+        PlantState::Standing => 'S',
+        PlantState::Harvested => 'H',
+        PlantState::Squashed => 'Q',
+    }
+}
+
+// e.g. "S120H16Q3", row by row
+pub fn encode_plants(grid: &CropGrid) -> String {
+    let mut encoded = String::new();
+    let mut run: Option<(char, usize)> = None;
+    for row in 0..grid.rows {
+        for column in 0..grid.columns {
+            let code = state_code(grid.plant_state(column, row));
+            run = match run {
+                Some((current, count)) if current == code => Some((current, count + 1)),
+                Some((current, count)) => {
+                    encoded.push_str(&format!("{}{}", current, count));
+                    Some((code, 1))
+                }
+                None => Some((code, 1)),
+            };
+        }
+    }
+    if let Some((current, count)) = run {
+        encoded.push_str(&format!("{}{}", current, count));
+    }
+    encoded
+}
+
+// anything that doesn't fit the grid is ignored, so a bad save just leaves crops standing
+pub fn decode_plants(encoded: &str, grid: &mut CropGrid) {
+    let mut index = 0;
+    let mut chars = encoded.chars().peekable();
+    while let Some(code) = chars.next() {
+        let state = match code {
+            'S' => PlantState::Standing,
+            'H' => PlantState::Harvested,
+            'Q' => PlantState::Squashed,
+            _ => return,
+        };
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            digits.push(digit);
+        }
+        let count: usize = match digits.parse() {
+            Ok(count) => count,
+            Err(_) => return,
+        };
+        for _ in 0..count {
+            if index >= grid.columns * grid.rows {
+                return;
+            }
+            grid.set_plant_state(index % grid.columns, index / grid.columns, state);
+            index += 1;
+        }
+    }
+}
+
+// puts back whatever was left of each field at the end of the last day
+pub fn restore_fields(
+    career: Res<Career>,
+    game: Res<game::Game>,
+    mut grids: Query<(Entity, &mut CropGrid), Added<CropGrid>>,
+) {
+    if game.level != Level::Career {
+        return;
+    }
+    for (entity, mut grid) in grids.iter_mut() {
+        let index = game.fields.iter().position(|field| field.crops == entity);
+        if let Some(plants) = index.and_then(|index| career.fields.get(index)) {
+            decode_plants(plants, &mut grid);
+        }
+    }
+}
+
+pub fn end_day(mut career: ResMut<Career>, game: Res<game::Game>, grids: Query<&CropGrid>) {
+    if game.level != Level::Career {
+        return;
+    }
+    career.money += game.score;
+    career.day += 1;
+    career.fields = game
+        .fields
+        .iter()
+        .map(|field| match grids.get(field.crops) {
+            Ok(grid) if grid.harvested_fraction() < RESOW_THRESHOLD => encode_plants(grid),
+            _ => String::new(),
+        })
+        .collect();
+    career.save();
+}
@@ -0,0 +1,235 @@
+use crate::ghost::Ghosts;
+use crate::replay::Replay;
+
+use bevy::prelude::*;
+
+const TOGGLE_KEY: KeyCode = KeyCode::Grave;
+// how much of what's been typed and printed stays on screen
+const VISIBLE_LINES: usize = 10;
+// how much is kept to scroll back through; older lines are dropped
+const SCROLLBACK_LINES: usize = 200;
+
+// takes the words after the command's name, and gives back what to print
+pub type CommandHandler = fn(&mut World, &[&str]) -> Result<String, String>;
+
+struct ConsoleCommand {
+    name: &'static str,
+    // the arguments, as shown by `help`
+    usage: &'static str,
+    run: CommandHandler,
+}
+
+// everything the console can do; modules add their own with add_console_command
+#[derive(Default)]
+pub struct ConsoleCommands {
+    commands: Vec<ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    pub fn register(&mut self, name: &'static str, usage: &'static str, run: CommandHandler) {
+        self.commands.retain(|command| command.name != name);
+        self.commands.push(ConsoleCommand { name, usage, run });
+    }
+
+    fn find(&self, name: &str) -> Option<CommandHandler> {
+        self.commands
+            .iter()
+            .find(|command| command.name == name)
+            .map(|command| command.run)
+    }
+
+    fn help(&self) -> String {
+        let mut lines: Vec<String> = self
+            .commands
+            .iter()
+            .map(|command| format!("{} {}", command.name, command.usage))
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+}
+
+pub trait ConsoleApp {
+    fn add_console_command(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        run: CommandHandler,
+    ) -> &mut Self;
+}
+
+impl ConsoleApp for App {
+    fn add_console_command(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        run: CommandHandler,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .register(name, usage, run);
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    lines: Vec<String>,
+    // entered, waiting for run_commands to get to it
+    submitted: Option<String>,
+}
+
+impl Console {
+    fn print(&mut self, text: &str) {
+        self.lines.extend(text.lines().map(str::to_string));
+        let excess = self.lines.len().saturating_sub(SCROLLBACK_LINES);
+        self.lines.drain(..excess);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConsoleInput;
+
+#[derive(Component)]
+pub struct ConsoleRoot {}
+
+#[derive(Component)]
+pub struct ConsoleText {}
+
+// runs before anything else reads the keyboard, so nothing typed here drives the vehicles
+pub fn console_input(
+    mut console: ResMut<Console>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+) {
+    if keyboard_input.just_pressed(TOGGLE_KEY) {
+        console.open = !console.open;
+        keyboard_input.clear();
+    }
+    if !console.open {
+        characters.clear();
+        return;
+    }
+
+    for event in characters.iter() {
+        if !event.char.is_control() && event.char != '`' {
+            console.input.push(event.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        if !line.trim().is_empty() {
+            console.submitted = Some(line);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        console.open = false;
+    }
+
+    let held: Vec<KeyCode> = keyboard_input.get_pressed().copied().collect();
+    for key in held {
+        keyboard_input.reset(key);
+    }
+    keyboard_input.clear();
+}
+
+// an exclusive system, as the commands can reach into anything
+pub fn run_commands(world: &mut World) {
+    let line = match world.resource_mut::<Console>().submitted.take() {
+        Some(line) => line,
+        None => return,
+    };
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let commands = world.resource::<ConsoleCommands>();
+    let result = match words[0] {
+        "help" => Ok(format!("help\n{}", commands.help())),
+        name => match commands.find(name) {
+            Some(run) => {
+                let result = run(world, &words[1..]);
+                // a round that's been meddled with can't be played back or raced fairly,
+                // but one where the command was turned away is as it was
+                if result.is_ok() {
+                    world.resource_mut::<Replay>().abandon();
+                    world.resource_mut::<Ghosts>().abandon();
+                }
+                result
+            }
+            None => Err(format!("unknown command {}, try help", name)),
+        },
+    };
+
+    let mut console = world.resource_mut::<Console>();
+    console.print(&format!("> {}", line));
+    match result {
+        Ok(output) => console.print(&output),
+        Err(error) => console.print(&format!("error: {}", error)),
+    }
+}
+
+// game::cleanup takes the overlay away with everything else, so it's put back when missing
+pub fn draw_console(
+    mut commands: Commands,
+    console: Res<Console>,
+    asset_server: Res<AssetServer>,
+    roots: Query<Entity, With<ConsoleRoot>>,
+    mut texts: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.open {
+        for root in roots.iter() {
+            commands.entity(root).despawn_recursive();
+        }
+        return;
+    }
+
+    let first = console.lines.len().saturating_sub(VISIBLE_LINES);
+    let mut shown = console.lines[first..].join("\n");
+    if !shown.is_empty() {
+        shown.push('\n');
+    }
+    shown.push_str(&format!("> {}_", console.input));
+
+    if let Ok(mut text) = texts.get_single_mut() {
+        if text.sections[0].value != shown {
+            text.sections[0].value = shown;
+        }
+        return;
+    }
+    if !roots.is_empty() {
+        // spawned this frame, and the text isn't there yet
+        return;
+    }
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.),
+                    bottom: Val::Px(0.),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            color: Color::rgba(0., 0., 0., 0.75).into(),
+            ..default()
+        })
+        .insert(ConsoleRoot {})
+        .with_children(|panel| {
+            panel
+                .spawn_bundle(TextBundle::from_section(
+                    shown,
+                    TextStyle {
+                        font: asset_server.load("fonts/abel-regular.ttf"),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(ConsoleText {});
+        });
+}
@@ -0,0 +1,173 @@
+use crate::crop_grid::CropGrid;
+use crate::drop_zone::GrainDeliveredEvent;
+use crate::game;
+use crate::game::ScoreChangeEvent;
+use crate::harvest::CropType;
+use crate::rng::{GameRng, RngStream};
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+// seconds between offers, and before the first one
+const OFFER_INTERVAL: f32 = 45.;
+const FIRST_OFFER: f32 = 10.;
+const MAX_ACTIVE: usize = 2;
+// orders are for this share of what's still standing in a field
+const MIN_SHARE: f32 = 0.3;
+const MAX_SHARE: f32 = 0.6;
+const SMALLEST_ORDER: i32 = 100;
+// time allowed, on top of a base, per unit ordered
+const BASE_DEADLINE: f32 = 60.;
+const SECONDS_PER_UNIT: f32 = 0.15;
+// reward as a multiple of the order's sale value, and penalty as a share of the reward
+const REWARD_FACTOR: f32 = 0.5;
+const PENALTY_FACTOR: f32 = 0.5;
+
+#[derive(Clone)]
+pub struct Contract {
+    pub crop: CropType,
+    pub amount: i32,
+    pub delivered: i32,
+    // seconds
+    pub time_left: f32,
+    pub reward: i32,
+    pub penalty: i32,
+}
+
+impl Contract {
+    pub fn describe(&self) -> String {
+        let seconds = self.time_left.max(0.) as u32;
+        format!(
+            "{}/{} {} in {}:{:02} (+{} / -{})",
+            self.delivered,
+            self.amount,
+            self.crop.info().name.to_lowercase(),
+            seconds / 60,
+            seconds % 60,
+            self.reward,
+            self.penalty
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Contracts {
+    pub active: Vec<Contract>,
+    pub completed: usize,
+    pub failed: usize,
+    // seconds until the next offer
+    pub time_to_offer: f32,
+}
+
+impl Default for Contracts {
+    fn default() -> Self {
+        Contracts {
+            active: Vec::new(),
+            completed: 0,
+            failed: 0,
+            time_to_offer: FIRST_OFFER,
+        }
+    }
+}
+
+pub fn setup(mut contracts: ResMut<Contracts>) {
+    *contracts = Contracts::default();
+}
+
+// orders come from what the map's fields can still supply, and have to fit in the round
+fn make_offer(
+    rng: &mut GameRng,
+    game: &game::Game,
+    grids: &Query<&CropGrid>,
+    active: &[Contract],
+) -> Option<Contract> {
+    let rng = rng.stream(RngStream::Contracts);
+    let round_left = game.time_remaining.as_secs_f32();
+    let field = game
+        .fields
+        .iter()
+        .filter(|field| !active.iter().any(|contract| contract.crop == field.crop))
+        .filter(|field| grids.get(field.crops).is_ok_and(|grid| grid.standing > 0))
+        .choose(rng)?;
+    let grid = grids.get(field.crops).ok()?;
+
+    let info = field.crop.info();
+    let available = grid.standing as f32 * info.crop_yield as f32;
+    let amount = (available * rng.gen_range(MIN_SHARE..MAX_SHARE) / 50.).round() as i32 * 50;
+    if amount < SMALLEST_ORDER {
+        return None;
+    }
+    let time_left = (BASE_DEADLINE + amount as f32 * SECONDS_PER_UNIT).min(round_left);
+    if time_left < BASE_DEADLINE {
+        return None;
+    }
+    let reward = (amount as f32 * info.value as f32 * REWARD_FACTOR) as i32;
+    Some(Contract {
+        crop: field.crop,
+        amount,
+        delivered: 0,
+        time_left,
+        reward,
+        penalty: (reward as f32 * PENALTY_FACTOR) as i32,
+    })
+}
+
+pub fn offer_contracts(
+    mut contracts: ResMut<Contracts>,
+    mut rng: ResMut<GameRng>,
+    game: Res<game::Game>,
+    grids: Query<&CropGrid>,
+    time: Res<Time>,
+) {
+    if !game.level.has_contracts() {
+        return;
+    }
+    contracts.time_to_offer -= time.delta_seconds();
+    if contracts.time_to_offer > 0. {
+        return;
+    }
+    contracts.time_to_offer = OFFER_INTERVAL;
+    if contracts.active.len() >= MAX_ACTIVE {
+        return;
+    }
+    if let Some(contract) = make_offer(&mut rng, &game, &grids, &contracts.active) {
+        contracts.active.push(contract);
+    }
+}
+
+// there's only ever one order per crop, so each delivery goes towards at most one
+pub fn track_contracts(
+    mut contracts: ResMut<Contracts>,
+    mut delivered_events: EventReader<GrainDeliveredEvent>,
+    mut score_events: EventWriter<ScoreChangeEvent>,
+    time: Res<Time>,
+) {
+    for event in delivered_events.iter() {
+        for contract in contracts.active.iter_mut() {
+            contract.delivered =
+                (contract.delivered + event.load.amount(contract.crop)).min(contract.amount);
+        }
+    }
+
+    let contracts = &mut *contracts;
+    let delta = time.delta_seconds();
+    let (completed, failed) = (&mut contracts.completed, &mut contracts.failed);
+    contracts.active.retain_mut(|contract| {
+        contract.time_left -= delta;
+        if contract.delivered >= contract.amount {
+            score_events.send(ScoreChangeEvent {
+                amount: contract.reward,
+            });
+            *completed += 1;
+            false
+        } else if contract.time_left <= 0. {
+            score_events.send(ScoreChangeEvent {
+                amount: -contract.penalty,
+            });
+            *failed += 1;
+            false
+        } else {
+            true
+        }
+    });
+}
@@ -0,0 +1,584 @@
+use crate::game;
+use crate::harvest::{CropHarvestedEvent, CropSquashedEvent, CropType, CORN_SIZE};
+use crate::weather::Weather;
+use std::collections::HashMap;
+
+use bevy::gltf::{Gltf, GltfMesh, GltfNode};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+
+// plants per side of a render chunk, so harvesting only rebuilds the bit that changed
+const CHUNK_PLANTS: usize = 16;
+// where the individual plant scenes used to sit
+const STANDING_HEIGHT: f32 = 0.7;
+const STUBBLE_HEIGHT: f32 = 0.1;
+
+const HARVESTED_MODEL: &str = "harvested_wheat.gltf";
+const SQUASHED_MODEL: &str = "squashed_wheat.gltf";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlantState {
+    Standing,
+    Harvested,
+    Squashed,
+}
+
+const PLANT_STATES: [PlantState; 3] = [
+    PlantState::Standing,
+    PlantState::Harvested,
+    PlantState::Squashed,
+];
+
+// a chunk's merged mesh for one part of one PlantState's model, and the entity drawing it
+type ChunkMesh = Option<(Entity, Handle<Mesh>)>;
+
+// every plant in a field, one byte each, rendered as a handful of merged chunk meshes
+#[derive(Component)]
+pub struct CropGrid {
+    pub crop_type: CropType,
+    origin: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    plants: Vec<PlantState>,
+    pub standing: usize,
+    rotation_seed: u32,
+    dirty_chunks: Vec<bool>,
+    // (entity, mesh) per chunk per PlantState per model part, once there's something to draw
+    chunk_meshes: Vec<[Vec<ChunkMesh>; 3]>,
+}
+
+// anything that flattens crops: the combine's header cuts them, truck wheels squash them
+#[derive(Component)]
+pub struct CropCutter {
+    // x across, y along the direction of travel
+    pub half_size: Vec2,
+    // how far ahead of the entity's origin the footprint sits
+    pub offset: f32,
+    pub action: PlantState,
+    pub enabled: bool,
+}
+
+// the geometry of a plant drawn with one material
+struct PlantPart {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+    material: Handle<StandardMaterial>,
+}
+
+// one part per material in the glTF, each merged into its own chunk meshes
+struct PlantModel {
+    parts: Vec<PlantPart>,
+}
+
+#[derive(Default)]
+pub struct PlantModels {
+    loading: HashMap<&'static str, Handle<Gltf>>,
+    models: HashMap<&'static str, PlantModel>,
+}
+
+impl CropGrid {
+    pub fn new(
+        crop_type: CropType,
+        origin: Vec2,
+        columns: usize,
+        rows: usize,
+        rotation_seed: u32,
+    ) -> Self {
+        let chunks = Self::chunks_across(columns) * Self::chunks_across(rows);
+        CropGrid {
+            crop_type,
+            origin,
+            columns,
+            rows,
+            plants: vec![PlantState::Standing; columns * rows],
+            standing: columns * rows,
+            rotation_seed,
+            dirty_chunks: vec![true; chunks],
+            chunk_meshes: vec![[Vec::new(), Vec::new(), Vec::new()]; chunks],
+        }
+    }
+
+    fn chunks_across(plants: usize) -> usize {
+        plants.div_ceil(CHUNK_PLANTS)
+    }
+
+    fn chunk_of(&self, column: usize, row: usize) -> usize {
+        (row / CHUNK_PLANTS) * Self::chunks_across(self.columns) + column / CHUNK_PLANTS
+    }
+
+    pub fn plant_position(&self, column: usize, row: usize) -> Vec3 {
+        Vec3::new(
+            self.origin.x + column as f32 * CORN_SIZE,
+            0.,
+            self.origin.y + row as f32 * CORN_SIZE,
+        )
+    }
+
+    pub fn plant_state(&self, column: usize, row: usize) -> PlantState {
+        self.plants[row * self.columns + column]
+    }
+
+    pub fn set_plant_state(&mut self, column: usize, row: usize, state: PlantState) {
+        let index = row * self.columns + column;
+        if self.plants[index] == state {
+            return;
+        }
+        if self.plants[index] == PlantState::Standing {
+            self.standing -= 1;
+        } else if state == PlantState::Standing {
+            self.standing += 1;
+        }
+        self.plants[index] = state;
+        let chunk = self.chunk_of(column, row);
+        self.dirty_chunks[chunk] = true;
+    }
+
+    // every plant back up, as it was sown
+    pub fn regrow(&mut self) {
+        self.plants.fill(PlantState::Standing);
+        self.standing = self.plants.len();
+        self.dirty_chunks.fill(true);
+    }
+
+    pub fn harvested_fraction(&self) -> f32 {
+        1. - self.standing as f32 / self.plants.len().max(1) as f32
+    }
+
+    pub fn standing_positions(&self) -> Vec<Vec3> {
+        let mut positions = Vec::with_capacity(self.standing);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                if self.plant_state(column, row) == PlantState::Standing {
+                    positions.push(self.plant_position(column, row));
+                }
+            }
+        }
+        positions
+    }
+
+    // same rotation every time a chunk is rebuilt, without having to store one per plant
+    fn plant_rotation(&self, column: usize, row: usize) -> f32 {
+        let mut hash = self.rotation_seed ^ (column as u32).wrapping_mul(73856093);
+        hash ^= (row as u32).wrapping_mul(19349663);
+        hash = hash.wrapping_mul(2654435761);
+        ((hash >> 8) % 360) as f32
+    }
+
+    // (column, row) of every standing plant inside the rectangle
+    fn standing_within(
+        &self,
+        centre: Vec2,
+        right: Vec2,
+        forward: Vec2,
+        half_size: Vec2,
+    ) -> Vec<(usize, usize)> {
+        let reach = Vec2::new(
+            right.x.abs() * half_size.x + forward.x.abs() * half_size.y,
+            right.y.abs() * half_size.x + forward.y.abs() * half_size.y,
+        );
+        let min = ((centre - reach - self.origin) / CORN_SIZE)
+            .ceil()
+            .max(Vec2::ZERO);
+        let max = (centre + reach - self.origin) / CORN_SIZE;
+        if max.x < 0. || max.y < 0. {
+            return Vec::new();
+        }
+        let max_column = (max.x.floor() as usize).min(self.columns.saturating_sub(1));
+        let max_row = (max.y.floor() as usize).min(self.rows.saturating_sub(1));
+
+        let mut within = Vec::new();
+        for row in min.y as usize..=max_row {
+            for column in min.x as usize..=max_column {
+                if self.plant_state(column, row) != PlantState::Standing {
+                    continue;
+                }
+                let position = self.plant_position(column, row);
+                let offset = Vec2::new(position.x, position.z) - centre;
+                if offset.dot(right).abs() <= half_size.x
+                    && offset.dot(forward).abs() <= half_size.y
+                {
+                    within.push((column, row));
+                }
+            }
+        }
+        within
+    }
+}
+
+fn model_path(crop_type: CropType, state: PlantState) -> &'static str {
+    match state {
+        PlantState::Standing => crop_type.info().model,
+        PlantState::Harvested => HARVESTED_MODEL,
+        PlantState::Squashed => SQUASHED_MODEL,
+    }
+}
+
+// both faces of a quad, so it shows from either side without touching the material
+fn push_quad(part: &mut PlantPart, corners: [Vec3; 4], uvs: [Vec2; 4]) {
+    let normal = (corners[1] - corners[0])
+        .cross(corners[3] - corners[0])
+        .normalize_or_zero();
+    for (normal, order) in [(normal, [0, 1, 2, 0, 2, 3]), (-normal, [0, 2, 1, 0, 3, 2])] {
+        let first_vertex = part.positions.len() as u32;
+        for (corner, uv) in corners.iter().zip(uvs) {
+            part.positions.push(corner.to_array());
+            part.normals.push(normal.to_array());
+            part.uvs.push(uv.to_array());
+        }
+        part.indices
+            .extend(order.iter().map(|index| first_vertex + index));
+    }
+}
+
+// a few quads in place of one of the glTF's primitives: flat over the top of anything
+// lying down, crossed upright through anything standing. Each quad spans the part of the
+// texture the primitive used, top to bottom and side to side
+fn push_proxy(part: &mut PlantPart, (min, max): (Vec3, Vec3), (uv_min, uv_max): (Vec2, Vec2)) {
+    let uvs = [
+        Vec2::new(uv_min.x, uv_max.y),
+        Vec2::new(uv_max.x, uv_max.y),
+        Vec2::new(uv_max.x, uv_min.y),
+        uv_min,
+    ];
+    let size = max - min;
+    if size.y < 0.25 * size.x.max(size.z) {
+        push_quad(
+            part,
+            [
+                Vec3::new(min.x, max.y, min.z),
+                Vec3::new(min.x, max.y, max.z),
+                Vec3::new(max.x, max.y, max.z),
+                Vec3::new(max.x, max.y, min.z),
+            ],
+            uvs,
+        );
+        return;
+    }
+    let centre = (min + max) / 2.;
+    push_quad(
+        part,
+        [
+            Vec3::new(min.x, min.y, centre.z),
+            Vec3::new(max.x, min.y, centre.z),
+            Vec3::new(max.x, max.y, centre.z),
+            Vec3::new(min.x, max.y, centre.z),
+        ],
+        uvs,
+    );
+    push_quad(
+        part,
+        [
+            Vec3::new(centre.x, min.y, min.z),
+            Vec3::new(centre.x, min.y, max.z),
+            Vec3::new(centre.x, max.y, max.z),
+            Vec3::new(centre.x, max.y, min.z),
+        ],
+        uvs,
+    );
+}
+
+// the smallest box around every value, or None if there aren't any
+fn bounds<T: Copy>(values: &[T], to_vec: impl Fn(T) -> Vec3) -> Option<(Vec3, Vec3)> {
+    let mut points = values.iter().map(|value| to_vec(*value));
+    let first = points.next()?;
+    Some(points.fold((first, first), |(min, max), point| {
+        (min.min(point), max.max(point))
+    }))
+}
+
+// a low-poly stand-in for the plant in a glTF file, one proxy per primitive in it sized to
+// that primitive's bounds and keeping its material; a chunk stamps it out once per plant,
+// and the full models have thousands of vertices each
+fn build_plant_model(
+    gltf: &Gltf,
+    nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+    meshes: &Assets<Mesh>,
+) -> Option<PlantModel> {
+    let mut model = PlantModel { parts: Vec::new() };
+    for node_handle in gltf.nodes.iter() {
+        let node = nodes.get(node_handle)?;
+        let gltf_mesh = match &node.mesh {
+            Some(handle) => gltf_meshes.get(handle)?,
+            None => continue,
+        };
+        let matrix = node.transform.compute_matrix();
+        for primitive in gltf_mesh.primitives.iter() {
+            let mesh = meshes.get(&primitive.mesh)?;
+            let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+                Some(VertexAttributeValues::Float32x3(positions)) => {
+                    bounds(positions, |p| matrix.transform_point3(Vec3::from(p)))
+                }
+                _ => None,
+            };
+            let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                Some(VertexAttributeValues::Float32x2(uvs)) => {
+                    bounds(uvs, |[u, v]| Vec3::new(u, v, 0.))
+                        .map(|(min, max)| (min.truncate(), max.truncate()))
+                }
+                _ => None,
+            };
+            let positions = match positions {
+                Some(positions) => positions,
+                None => continue,
+            };
+
+            // primitives sharing a material are drawn together
+            let material = primitive.material.clone().unwrap_or_default();
+            let index = match model
+                .parts
+                .iter()
+                .position(|part| part.material == material)
+            {
+                Some(index) => index,
+                None => {
+                    model.parts.push(PlantPart {
+                        positions: Vec::new(),
+                        normals: Vec::new(),
+                        uvs: Vec::new(),
+                        indices: Vec::new(),
+                        material,
+                    });
+                    model.parts.len() - 1
+                }
+            };
+            push_proxy(
+                &mut model.parts[index],
+                positions,
+                uvs.unwrap_or((Vec2::ZERO, Vec2::ONE)),
+            );
+        }
+    }
+    Some(model)
+}
+
+fn build_chunk_mesh(
+    grid: &CropGrid,
+    chunk: usize,
+    state: PlantState,
+    part: &PlantPart,
+) -> Option<Mesh> {
+    let chunks_across = CropGrid::chunks_across(grid.columns);
+    let first_column = (chunk % chunks_across) * CHUNK_PLANTS;
+    let first_row = (chunk / chunks_across) * CHUNK_PLANTS;
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut indices = Vec::new();
+    for row in first_row..(first_row + CHUNK_PLANTS).min(grid.rows) {
+        for column in first_column..(first_column + CHUNK_PLANTS).min(grid.columns) {
+            if grid.plant_state(column, row) != state {
+                continue;
+            }
+            let (height, rotation) = match state {
+                PlantState::Standing => (STANDING_HEIGHT, grid.plant_rotation(column, row)),
+                _ => (STUBBLE_HEIGHT, 0.),
+            };
+            let transform = Transform {
+                translation: grid.plant_position(column, row) + Vec3::Y * height,
+                rotation: Quat::from_rotation_y(rotation.to_radians()),
+                ..default()
+            };
+
+            let first_vertex = positions.len() as u32;
+            for position in part.positions.iter() {
+                positions.push((transform * Vec3::from(*position)).to_array());
+            }
+            for normal in part.normals.iter() {
+                normals.push((transform.rotation * Vec3::from(*normal)).to_array());
+            }
+            uvs.extend(part.uvs.iter());
+            indices.extend(part.indices.iter().map(|index| first_vertex + index));
+        }
+    }
+    if positions.is_empty() {
+        return None;
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    Some(mesh)
+}
+
+pub fn load_plant_models(
+    grids: Query<&CropGrid, Added<CropGrid>>,
+    mut plant_models: ResMut<PlantModels>,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for grid in grids.iter() {
+        for state in PLANT_STATES {
+            let path = model_path(grid.crop_type, state);
+            if !plant_models.models.contains_key(path) && !plant_models.loading.contains_key(path) {
+                plant_models.loading.insert(path, asset_server.load(path));
+            }
+        }
+    }
+
+    let mut loaded = Vec::new();
+    for (path, handle) in plant_models.loading.iter() {
+        if let Some(gltf) = gltfs.get(handle) {
+            if let Some(model) = build_plant_model(gltf, &nodes, &gltf_meshes, &meshes) {
+                loaded.push((*path, model));
+            }
+        }
+    }
+    for (path, model) in loaded {
+        plant_models.loading.remove(path);
+        plant_models.models.insert(path, model);
+    }
+}
+
+pub fn rebuild_crop_meshes(
+    mut commands: Commands,
+    mut grids: Query<(Entity, &mut CropGrid)>,
+    plant_models: Res<PlantModels>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (grid_entity, mut grid) in grids.iter_mut() {
+        if !grid.dirty_chunks.iter().any(|dirty| *dirty) {
+            continue;
+        }
+        let crop_type = grid.crop_type;
+        if PLANT_STATES.iter().any(|state| {
+            !plant_models
+                .models
+                .contains_key(model_path(crop_type, *state))
+        }) {
+            continue;
+        }
+
+        for chunk in 0..grid.dirty_chunks.len() {
+            if !grid.dirty_chunks[chunk] {
+                continue;
+            }
+            for (index, state) in PLANT_STATES.iter().enumerate() {
+                let model = &plant_models.models[model_path(crop_type, *state)];
+                grid.chunk_meshes[chunk][index].resize(model.parts.len(), None);
+                for (part_index, part) in model.parts.iter().enumerate() {
+                    let mesh = build_chunk_mesh(&grid, chunk, *state, part);
+                    let existing = grid.chunk_meshes[chunk][index][part_index].clone();
+                    match (mesh, existing) {
+                        (Some(mesh), Some((_, handle))) => {
+                            if let Some(old_mesh) = meshes.get_mut(&handle) {
+                                *old_mesh = mesh;
+                            }
+                        }
+                        (Some(mesh), None) => {
+                            let handle = meshes.add(mesh);
+                            let entity = commands
+                                .spawn_bundle(PbrBundle {
+                                    mesh: handle.clone(),
+                                    material: part.material.clone(),
+                                    ..default()
+                                })
+                                .id();
+                            commands.entity(grid_entity).add_child(entity);
+                            grid.chunk_meshes[chunk][index][part_index] = Some((entity, handle));
+                        }
+                        (None, Some((entity, _))) => {
+                            commands.entity(entity).despawn();
+                            grid.chunk_meshes[chunk][index][part_index] = None;
+                        }
+                        (None, None) => {}
+                    }
+                }
+            }
+            grid.dirty_chunks[chunk] = false;
+        }
+    }
+}
+
+pub fn cut_crops(
+    mut grids: Query<(Entity, &mut CropGrid)>,
+    cutters: Query<(&CropCutter, &GlobalTransform)>,
+    mut crop_harvested_events: EventWriter<CropHarvestedEvent>,
+    mut crop_squashed_events: EventWriter<CropSquashedEvent>,
+    weather: Res<Weather>,
+) {
+    for (cutter, global_transform) in cutters.iter() {
+        if !cutter.enabled {
+            continue;
+        }
+        // a wet crop just clogs the header, but wheels flatten it all the same
+        if cutter.action == PlantState::Harvested && weather.crop_wet() {
+            continue;
+        }
+        let transform = global_transform.compute_transform();
+        let forward = Vec2::new(transform.forward().x, transform.forward().z).normalize_or_zero();
+        let right = Vec2::new(transform.right().x, transform.right().z).normalize_or_zero();
+        let centre =
+            Vec2::new(transform.translation.x, transform.translation.z) + forward * cutter.offset;
+
+        // look without touching, so only a grid that actually loses a plant counts as changed
+        let reached: Vec<(Entity, Vec<(usize, usize)>)> = grids
+            .iter()
+            .map(|(entity, grid)| {
+                (
+                    entity,
+                    grid.standing_within(centre, right, forward, cutter.half_size),
+                )
+            })
+            .filter(|(_, plants)| !plants.is_empty())
+            .collect();
+        for (entity, plants) in reached {
+            let mut grid = match grids.get_mut(entity) {
+                Ok((_, grid)) => grid,
+                Err(_) => continue,
+            };
+            let crop_type = grid.crop_type;
+            for (column, row) in plants {
+                grid.set_plant_state(column, row, cutter.action);
+                let position = grid.plant_position(column, row);
+                match cutter.action {
+                    PlantState::Harvested => crop_harvested_events.send(CropHarvestedEvent {
+                        position,
+                        crop_type,
+                        amount: crop_type.info().crop_yield,
+                    }),
+                    PlantState::Squashed => {
+                        crop_squashed_events.send(CropSquashedEvent { position })
+                    }
+                    PlantState::Standing => {}
+                }
+            }
+        }
+    }
+}
+
+// console: `regrow` for every field, or `regrow 2` for the second
+pub fn console_regrow(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let fields: Vec<Entity> = world
+        .resource::<game::Game>()
+        .fields
+        .iter()
+        .map(|field| field.crops)
+        .collect();
+    let chosen = match args {
+        [] => fields,
+        [number] => {
+            let index = number
+                .parse::<usize>()
+                .ok()
+                .filter(|number| (1..=fields.len()).contains(number))
+                .ok_or_else(|| format!("the field number goes from 1 to {}", fields.len()))?;
+            vec![fields[index - 1]]
+        }
+        _ => return Err("regrow takes at most a field number".to_string()),
+    };
+    for entity in chosen.iter() {
+        if let Some(mut grid) = world.get_mut::<CropGrid>(*entity) {
+            grid.regrow();
+        }
+    }
+    Ok(format!("regrew {} field(s)", chosen.len()))
+}
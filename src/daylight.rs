@@ -0,0 +1,130 @@
+use crate::game;
+use crate::weather::Weather;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use bevy::prelude::*;
+
+// the round runs from mid afternoon to well after dark
+pub const START_HOUR: f32 = 15.;
+pub const END_HOUR: f32 = 22.;
+const SUNRISE: f32 = 6.;
+const SUNSET: f32 = 20.;
+// how high (as the sine of the angle) the sun has to be before it's fully bright
+const FULL_DAYLIGHT_ELEVATION: f32 = 0.3;
+// swings the sun's path round towards the south
+const SUN_TILT: f32 = 0.4;
+
+const DAY_AMBIENT: f32 = 0.05;
+const NIGHT_AMBIENT: f32 = 0.01;
+const SUNSET_COLOR: Color = Color::rgb(1., 0.55, 0.3);
+
+const HEADLIGHT_INTENSITY: f32 = 6000.;
+const HEADLIGHT_RANGE: f32 = 40.;
+// below this much light the headlights come on
+const HEADLIGHT_THRESHOLD: f32 = 0.5;
+
+#[derive(Component)]
+pub struct Headlight {}
+
+pub struct TimeOfDay {
+    pub hour: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay { hour: START_HOUR }
+    }
+}
+
+impl TimeOfDay {
+    // 0 at sunrise, PI at sunset
+    fn sun_angle(&self) -> f32 {
+        PI * (self.hour - SUNRISE) / (SUNSET - SUNRISE)
+    }
+
+    // 0 at night up to 1 in full daylight
+    pub fn daylight(&self) -> f32 {
+        (self.sun_angle().sin() / FULL_DAYLIGHT_ELEVATION).clamp(0., 1.)
+    }
+
+    pub fn clock(&self) -> String {
+        let minutes = (self.hour * 60.) as u32;
+        format!("{:02}:{:02}", (minutes / 60) % 24, minutes % 60)
+    }
+}
+
+// a pair of lights either side of the front, looking along -z like the vehicles do
+pub fn spawn_headlights(commands: &mut Commands, vehicle: Entity, half_width: f32, front: f32) {
+    for side in [-1., 1.] {
+        let position = Vec3::new(side * half_width, 1.5, front);
+        let headlight = commands
+            .spawn_bundle(SpotLightBundle {
+                spot_light: SpotLight {
+                    intensity: 0.,
+                    range: HEADLIGHT_RANGE,
+                    color: Color::rgb(1., 0.95, 0.8),
+                    outer_angle: FRAC_PI_4 / 1.5,
+                    inner_angle: FRAC_PI_4 / 3.,
+                    shadows_enabled: false,
+                    ..default()
+                },
+                transform: Transform::from_translation(position)
+                    .looking_at(position + Vec3::new(0., -1.5, -10.), Vec3::Y),
+                ..default()
+            })
+            .insert(Headlight {})
+            .id();
+        commands.entity(vehicle).add_child(headlight);
+    }
+}
+
+pub fn setup(mut time_of_day: ResMut<TimeOfDay>) {
+    *time_of_day = TimeOfDay::default();
+}
+
+pub fn update_time_of_day(game: Res<game::Game>, mut time_of_day: ResMut<TimeOfDay>) {
+    let round_length = game.round_length.as_secs_f32();
+    if round_length > 0. {
+        let elapsed = 1. - game.time_remaining.as_secs_f32() / round_length;
+        time_of_day.hour = START_HOUR + (END_HOUR - START_HOUR) * elapsed;
+    }
+}
+
+pub fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    weather: Res<Weather>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut suns: Query<(&mut DirectionalLight, &mut Transform)>,
+) {
+    let sun_angle = time_of_day.sun_angle().clamp(0., PI);
+    let daylight = time_of_day.daylight();
+    let light_factor = daylight * weather.light_factor();
+    for (mut light, mut transform) in suns.iter_mut() {
+        // rises in the east (-x) and sets in the west
+        transform.rotation =
+            Quat::from_rotation_y(-FRAC_PI_2 - SUN_TILT) * Quat::from_rotation_x(-sun_angle);
+        light.illuminance = game::SUN_ILLUMINANCE * light_factor;
+        // low sun is orange
+        let warmth = 1. - sun_angle.sin();
+        light.color = Color::WHITE * (1. - warmth) + SUNSET_COLOR * warmth;
+    }
+    ambient_light.brightness =
+        NIGHT_AMBIENT + (DAY_AMBIENT - NIGHT_AMBIENT) * daylight * weather.light_factor();
+    ambient_light.color = if daylight > 0. {
+        Color::WHITE
+    } else {
+        // moonlight
+        Color::rgb(0.6, 0.7, 1.)
+    };
+}
+
+pub fn update_headlights(
+    time_of_day: Res<TimeOfDay>,
+    weather: Res<Weather>,
+    mut headlights: Query<&mut SpotLight, With<Headlight>>,
+) {
+    let on = time_of_day.daylight() * weather.light_factor() < HEADLIGHT_THRESHOLD;
+    for mut headlight in headlights.iter_mut() {
+        headlight.intensity = if on { HEADLIGHT_INTENSITY } else { 0. };
+    }
+}
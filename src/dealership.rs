@@ -0,0 +1,327 @@
+use crate::game::GameState;
+use crate::menu::{spawn_button, text_style, ButtonSize, BUTTON_COLOR, HOVERED_COLOR};
+use crate::vehicles::Vehicle;
+
+use bevy::prelude::*;
+
+const SELECTED_COLOR: Color = Color::rgb(0.6, 0.5, 0.1);
+
+// compiled in, so the web build doesn't have to fetch it before the menu
+const CATALOGUE: &str = include_str!("../assets/vehicles.catalogue");
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VehicleClass {
+    Combine,
+    Truck,
+}
+
+pub struct VehicleModel {
+    pub class: VehicleClass,
+    pub name: String,
+    pub description: String,
+    pub scene: String,
+    pub half_extents: Vec3,
+    pub drive_speed: f32,
+    pub turn_rate: f32,
+    pub acceleration: f32,
+    pub max_reverse: f32,
+    pub wheelbase: f32,
+    pub max_steering_angle: f32,
+    pub lateral_grip: f32,
+    pub braking: f32,
+    pub capacity: i32,
+    // auger speed for a combine, dump speed for a truck
+    pub unload_speed: f32,
+}
+
+impl VehicleModel {
+    fn new(class: VehicleClass, name: &str) -> Self {
+        VehicleModel {
+            class,
+            name: name.to_string(),
+            description: String::new(),
+            scene: String::new(),
+            half_extents: Vec3::ONE,
+            drive_speed: 10.,
+            turn_rate: 2.,
+            acceleration: 20.,
+            max_reverse: 5.,
+            wheelbase: 3.,
+            max_steering_angle: 0.6,
+            lateral_grip: 8.,
+            braking: 25.,
+            capacity: 500,
+            unload_speed: 100.,
+        }
+    }
+
+    pub fn vehicle(&self) -> Vehicle {
+        Vehicle {
+            drive_speed: self.drive_speed,
+            turn_rate: self.turn_rate,
+            acceleration: self.acceleration,
+            max_reverse: self.max_reverse,
+            wheelbase: self.wheelbase,
+            max_steering_angle: self.max_steering_angle,
+            lateral_grip: self.lateral_grip,
+            braking: self.braking,
+            ..default()
+        }
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{}: {} (top speed {}, holds {})",
+            self.name, self.description, self.drive_speed, self.capacity
+        )
+    }
+}
+
+pub struct VehicleCatalogue {
+    pub models: Vec<VehicleModel>,
+}
+
+impl Default for VehicleCatalogue {
+    fn default() -> Self {
+        match parse_catalogue(CATALOGUE) {
+            Ok(models) => VehicleCatalogue { models },
+            Err(error) => panic!("assets/vehicles.catalogue: {}", error),
+        }
+    }
+}
+
+impl VehicleCatalogue {
+    // indices into `models` of everything in the class, in catalogue order
+    pub fn of_class(&self, class: VehicleClass) -> Vec<usize> {
+        (0..self.models.len())
+            .filter(|index| self.models[*index].class == class)
+            .collect()
+    }
+
+    // the first of its class if nothing (or the wrong class) was chosen
+    fn model_index(&self, class: VehicleClass, chosen: Option<usize>) -> usize {
+        chosen
+            .filter(|index| self.models.get(*index).map(|model| model.class) == Some(class))
+            .or_else(|| self.models.iter().position(|model| model.class == class))
+            .expect("the catalogue needs at least one combine and one truck")
+    }
+
+    pub fn model(&self, class: VehicleClass, chosen: Option<usize>) -> &VehicleModel {
+        &self.models[self.model_index(class, chosen)]
+    }
+}
+
+fn parse_catalogue(contents: &str) -> Result<Vec<VehicleModel>, String> {
+    let mut models: Vec<VehicleModel> = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = value.trim();
+        let error = |problem: &str| format!("line {}: {}", number + 1, problem);
+        let number_value = || {
+            value
+                .parse::<f32>()
+                .map_err(|_| error(&format!("{} isn't a number", value)))
+        };
+
+        match key {
+            "combine" => models.push(VehicleModel::new(VehicleClass::Combine, value)),
+            "truck" => models.push(VehicleModel::new(VehicleClass::Truck, value)),
+            _ => {
+                let model = models
+                    .last_mut()
+                    .ok_or_else(|| error("stats before any combine or truck"))?;
+                match key {
+                    "description" => model.description = value.to_string(),
+                    "scene" => model.scene = value.to_string(),
+                    "collider" => {
+                        let sizes = value
+                            .split_whitespace()
+                            .map(|size| size.parse::<f32>())
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(|_| error("collider sizes must be numbers"))?;
+                        if sizes.len() != 3 {
+                            return Err(error("collider needs x, y and z"));
+                        }
+                        model.half_extents = Vec3::new(sizes[0], sizes[1], sizes[2]);
+                    }
+                    "drive_speed" => model.drive_speed = number_value()?,
+                    "turn_rate" => model.turn_rate = number_value()?,
+                    "acceleration" => model.acceleration = number_value()?,
+                    "max_reverse" => model.max_reverse = number_value()?,
+                    "wheelbase" => model.wheelbase = number_value()?,
+                    "max_steering_angle" => model.max_steering_angle = number_value()?,
+                    "lateral_grip" => model.lateral_grip = number_value()?,
+                    "braking" => model.braking = number_value()?,
+                    "capacity" => model.capacity = number_value()? as i32,
+                    "unload_speed" => model.unload_speed = number_value()?,
+                    _ => return Err(error(&format!("unknown stat {}", key))),
+                }
+            }
+        }
+    }
+    for class in [VehicleClass::Combine, VehicleClass::Truck] {
+        if !models.iter().any(|model| model.class == class) {
+            return Err(format!("no {:?} models", class));
+        }
+    }
+    if let Some(model) = models.iter().find(|model| model.scene.is_empty()) {
+        return Err(format!("{} has no scene", model.name));
+    }
+    Ok(models)
+}
+
+// what gets spawned in vehicles::setup, as indices into the catalogue
+#[derive(Default)]
+pub struct VehicleChoice {
+    pub combine: Option<usize>,
+    pub truck: Option<usize>,
+}
+
+impl VehicleChoice {
+    fn get(&self, class: VehicleClass) -> Option<usize> {
+        match class {
+            VehicleClass::Combine => self.combine,
+            VehicleClass::Truck => self.truck,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct DealershipRoot {}
+
+#[derive(Component)]
+pub struct DealershipCamera {}
+
+#[derive(Component)]
+pub struct ModelButton {
+    index: usize,
+    class: VehicleClass,
+}
+
+#[derive(Component)]
+pub struct StartButton {}
+
+fn button_size(width: f32) -> ButtonSize {
+    ButtonSize {
+        width,
+        height: 44.,
+        margin: 5.,
+        font_size: 22.,
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    catalogue: Res<VehicleCatalogue>,
+) {
+    let font_handle = asset_server.load("fonts/abel-regular.ttf");
+
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(DealershipCamera {});
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgb(0.1, 0.2, 0.1).into(),
+            ..default()
+        })
+        .insert(DealershipRoot {})
+        .with_children(|parent| {
+            for (class, heading) in [
+                (VehicleClass::Combine, "Pick a combine"),
+                (VehicleClass::Truck, "Pick a truck"),
+            ] {
+                parent.spawn_bundle(TextBundle::from_section(
+                    heading,
+                    text_style(&font_handle, 40.),
+                ));
+                for index in catalogue.of_class(class) {
+                    spawn_button(
+                        parent,
+                        &font_handle,
+                        &catalogue.models[index].summary(),
+                        button_size(620.),
+                        BUTTON_COLOR,
+                        ModelButton { index, class },
+                    );
+                }
+            }
+            spawn_button(
+                parent,
+                &font_handle,
+                "Start (Enter)",
+                button_size(260.),
+                BUTTON_COLOR,
+                StartButton {},
+            );
+            parent.spawn_bundle(TextBundle::from_section(
+                "Esc goes back to the menu",
+                text_style(&font_handle, 20.),
+            ));
+        });
+}
+
+type DealershipRoots<'w, 's> =
+    Query<'w, 's, Entity, Or<(With<DealershipRoot>, With<DealershipCamera>)>>;
+
+pub fn teardown(mut commands: Commands, roots: DealershipRoots) {
+    for entity in roots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn dealership_input(
+    mut choice: ResMut<VehicleChoice>,
+    catalogue: Res<VehicleCatalogue>,
+    mut app_state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut model_buttons: Query<(&Interaction, &ModelButton, &mut UiColor), Without<StartButton>>,
+    mut start_buttons: Query<(&Interaction, &mut UiColor), With<StartButton>>,
+) {
+    for (interaction, button, _) in model_buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            match button.class {
+                VehicleClass::Combine => choice.combine = Some(button.index),
+                VehicleClass::Truck => choice.truck = Some(button.index),
+            }
+        }
+    }
+    for (interaction, button, mut color) in model_buttons.iter_mut() {
+        let chosen = catalogue.model_index(button.class, choice.get(button.class));
+        color.0 = if chosen == button.index {
+            SELECTED_COLOR
+        } else if *interaction == Interaction::Hovered {
+            HOVERED_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+
+    let mut start = keyboard_input.just_pressed(KeyCode::Return);
+    for (interaction, mut color) in start_buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => start = true,
+            Interaction::Hovered => color.0 = HOVERED_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+    if start {
+        keyboard_input.clear();
+        app_state.set(GameState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.clear();
+        app_state.set(GameState::Menu).unwrap();
+    }
+}
@@ -1,8 +1,11 @@
+use crate::autopilot::{TruckAutopilot, TruckTask};
 use crate::game;
+use crate::harvest::CropLoad;
 use crate::vehicles;
 
 use crate::game::ScoreChangeEvent;
 use crate::vehicles::{CombineStorage, TruckStorage};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use heron::prelude::*;
 
@@ -12,7 +15,16 @@ pub struct DropZone {
     pub truck_in_zone: bool,
 }
 
-const DROP_ZONE_SIZE: f32 = 40.;
+pub const DROP_ZONE_SIZE: f32 = 40.;
+// where the grain falls from, above whatever is unloading
+const POUR_HEIGHT: f32 = 4.;
+// how far the truck's tipper has to rise, in degrees, before the load's all out
+const FULL_TIP: f32 = 60.;
+
+pub struct GrainDeliveredEvent {
+    pub position: Vec3,
+    pub load: CropLoad,
+}
 
 pub fn setup(
     mut commands: Commands,
@@ -26,7 +38,7 @@ pub fn setup(
             })),
             material: materials.add(Color::rgba(0.8, 0.2, 0.2, 0.1).into()),
             transform: Transform {
-                translation: Vec3::new(75., 0.1, 0.).into(),
+                translation: Vec3::new(75., 0.1, 0.),
                 ..default()
             },
             ..default()
@@ -54,16 +66,16 @@ pub fn setup(
 
 pub fn drop_zone_update(
     mut query: Query<(&mut DropZone, &Collisions)>,
-    combines: Query<(&vehicles::Combine)>,
-    trucks: Query<(&vehicles::Truck)>,
+    combines: Query<&vehicles::Combine>,
+    trucks: Query<&vehicles::Truck>,
 ) {
     for (mut drop_zone, collisions) in query.iter_mut() {
         let mut combine_found: bool = false;
         let mut truck_found: bool = false;
-        for entity in collisions.iter() {
-            if let Ok(combine) = combines.get_component::<vehicles::Combine>(entity) {
+        for entity in collisions.entities() {
+            if combines.contains(entity) {
                 combine_found = true;
-            } else if let Ok(truck) = trucks.get_component::<vehicles::Truck>(entity) {
+            } else if trucks.contains(entity) {
                 truck_found = true;
             }
         }
@@ -73,27 +85,91 @@ pub fn drop_zone_update(
     }
 }
 
+// the grain that can be handed in, and everything that hears about it
+#[derive(SystemParam)]
+pub struct Deliveries<'w, 's> {
+    pub combine_store: ResMut<'w, CombineStorage>,
+    pub truck_store: ResMut<'w, TruckStorage>,
+    pub score_event: EventWriter<'w, 's, ScoreChangeEvent>,
+    pub delivered_event: EventWriter<'w, 's, GrainDeliveredEvent>,
+}
+
 pub fn drop_zone_accept(
-    mut query: Query<&mut DropZone>,
-    mut combine_store: ResMut<CombineStorage>,
-    mut truck_store: ResMut<TruckStorage>,
-    mut score_event: EventWriter<ScoreChangeEvent>,
+    query: Query<&DropZone>,
+    deliveries: Deliveries,
+    combines: Query<&Transform, With<vehicles::Combine>>,
+    mut trucks: Query<(&mut vehicles::Truck, &Transform)>,
+    truck_autopilot: Res<TruckAutopilot>,
     keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
 ) {
+    let Deliveries {
+        mut combine_store,
+        mut truck_store,
+        mut score_event,
+        mut delivered_event,
+    } = deliveries;
     if let Ok(drop_zone) = query.get_single() {
-        if keyboard_input.pressed(KeyCode::Space) {
-            if drop_zone.combine_in_zone {
-                score_event.send(ScoreChangeEvent {
-                    amount: combine_store.contents,
-                });
-                combine_store.contents = 0;
+        let unloading = keyboard_input.pressed(KeyCode::Space);
+        if unloading && drop_zone.combine_in_zone {
+            if let Ok(transform) = combines.get_single() {
+                if combine_store.contents.total() > 0 {
+                    delivered_event.send(GrainDeliveredEvent {
+                        position: transform.translation + Vec3::Y * POUR_HEIGHT,
+                        load: combine_store.contents,
+                    });
+                }
             }
-            if drop_zone.truck_in_zone {
-                score_event.send(ScoreChangeEvent {
-                    amount: truck_store.contents,
-                });
-                truck_store.contents = 0;
+            score_event.send(ScoreChangeEvent {
+                amount: combine_store.contents.value(),
+            });
+            combine_store.contents.clear();
+        }
+        // the truck autopilot only parks in the zone, and leaves the tipping to us
+        let truck_delivering =
+            truck_autopilot.enabled && truck_autopilot.task == TruckTask::Delivering;
+        if let Ok((mut truck, transform)) = trucks.get_single_mut() {
+            if !drop_zone.truck_in_zone {
+                // driving off with the tipper part way up starts it over
+                truck.tipped = 0.;
+            } else if unloading || truck_delivering {
+                tip_truck(
+                    &mut truck,
+                    &mut truck_store,
+                    transform.translation,
+                    time.delta_seconds(),
+                    &mut score_event,
+                    &mut delivered_event,
+                );
             }
         }
     }
 }
+
+// raises the tipper a frame's worth, and delivers the load once it's all the way up
+fn tip_truck(
+    truck: &mut vehicles::Truck,
+    truck_store: &mut TruckStorage,
+    truck_position: Vec3,
+    delta: f32,
+    score_event: &mut EventWriter<ScoreChangeEvent>,
+    delivered_event: &mut EventWriter<GrainDeliveredEvent>,
+) {
+    if truck_store.contents.total() == 0 {
+        truck.tipped = 0.;
+        return;
+    }
+    truck.tipped += truck.dump_speed * delta;
+    if truck.tipped < FULL_TIP {
+        return;
+    }
+    truck.tipped = 0.;
+    delivered_event.send(GrainDeliveredEvent {
+        position: truck_position + Vec3::Y * POUR_HEIGHT,
+        load: truck_store.contents,
+    });
+    score_event.send(ScoreChangeEvent {
+        amount: truck_store.contents.value(),
+    });
+    truck_store.contents.clear();
+}
@@ -3,24 +3,67 @@ use heron::prelude::*;
 use std::f32::consts::FRAC_PI_2;
 use std::time;
 
-use rand::distributions::{Distribution, Uniform};
-
+use crate::crop_grid;
 use crate::harvest;
 use crate::harvest::CORN_SIZE;
+use crate::layout::FieldLayout;
+use crate::options::Options;
+use crate::rng::{GameRng, RngStream};
+use rand::Rng;
 
 const SECONDS_ON_TIMER: u64 = 300;
+const SECONDS_ON_TUTORIAL: u64 = 900;
+// a day: longer than anyone will play, and well within what a Duration can hold
+pub const MAX_ROUND_SECONDS: f32 = 86_400.;
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub enum GameState {
+    Menu,
+    // spending career money between days
+    Shop,
+    // picking which combine and truck to play with
+    Dealership,
     Playing,
+    // clearing the round away to play its replay again from the start
+    Rewinding,
     GameOver,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Level {
+    #[default]
+    Harvest,
+    // a single field and no distractions, with step-by-step instructions
+    Tutorial,
+    // one day of a longer run, where the money and fields carry over
+    Career,
+}
+
+impl Level {
+    pub fn round_length(&self) -> time::Duration {
+        match self {
+            Level::Harvest | Level::Career => time::Duration::new(SECONDS_ON_TIMER, 0),
+            Level::Tutorial => time::Duration::new(SECONDS_ON_TUTORIAL, 0),
+        }
+    }
+
+    pub fn has_weather(&self) -> bool {
+        *self != Level::Tutorial
+    }
+
+    pub fn has_herding(&self) -> bool {
+        *self != Level::Tutorial
+    }
+
+    pub fn has_contracts(&self) -> bool {
+        *self != Level::Tutorial
+    }
+}
+
 #[derive(PhysicsLayer)]
 pub enum GameLayer {
     Combine,
     Truck,
     Vehicle,
-    Crop,
     Obstacle,
     World,
     Animal,
@@ -31,25 +74,71 @@ pub struct Game {
     pub combine: Option<Entity>,
     pub truck: Option<Entity>,
     pub score: i32,
-    map: PbrBundle,
-    camera: Camera3dBundle,
     light: Option<Entity>,
     pub time_remaining: time::Duration,
+    pub round_length: time::Duration,
+    pub level: Level,
+    pub fields: Vec<Field>,
 }
 
+#[derive(Component)]
+pub struct Fence {}
+
 pub struct ScoreChangeEvent {
     pub amount: i32,
 }
 
-#[derive(PartialEq)]
-enum CompassPoint {
+#[derive(Clone, Copy, PartialEq)]
+pub enum CompassPoint {
     North,
     East,
     South,
     West,
 }
 
-const GROUND_HALF_SIZE: i32 = 250;
+impl CompassPoint {
+    // 'east' is towards -x, matching the way create_fences lays them out
+    pub fn direction(&self) -> Vec3 {
+        match self {
+            CompassPoint::North => Vec3::Z,
+            CompassPoint::East => -Vec3::X,
+            CompassPoint::South => -Vec3::Z,
+            CompassPoint::West => Vec3::X,
+        }
+    }
+}
+
+pub struct Field {
+    pub position: Vec2,
+    pub half_size: Vec2,
+    pub entrance: CompassPoint,
+    pub crop: harvest::CropType,
+    // the entity holding this field's CropGrid
+    pub crops: Entity,
+}
+
+impl Field {
+    pub fn contains(&self, point: Vec3) -> bool {
+        (point.x - self.position.x).abs() <= self.half_size.x + FIELD_BORDER
+            && (point.z - self.position.y).abs() <= self.half_size.y + FIELD_BORDER
+    }
+
+    // a point in the middle of the gateway, `offset` units outside the fence line
+    // (negative offsets are inside the field)
+    pub fn gate_position(&self, offset: f32) -> Vec3 {
+        let direction = self.entrance.direction();
+        let centre = Vec3::new(self.position.x, 0., self.position.y);
+        let half_extent = Vec3::new(
+            self.half_size.x + FIELD_BORDER,
+            0.,
+            self.half_size.y + FIELD_BORDER,
+        );
+        centre + direction * (half_extent.dot(direction.abs()) + offset)
+    }
+}
+
+pub const GROUND_HALF_SIZE: i32 = 250;
+pub const SUN_ILLUMINANCE: f32 = 30000.;
 const FIELD_BORDER: f32 = 2.;
 const FENCE_SIZE: f32 = 2.;
 const GATE_HALF_WIDTH: f32 = 6.;
@@ -65,12 +154,13 @@ fn spawn_fence(
         .spawn_bundle(SceneBundle {
             scene: asset_server.load("fence.gltf#Scene0"),
             transform: Transform {
-                translation: Vec3::new(x, 0.0, z).into(),
+                translation: Vec3::new(x, 0.0, z),
                 rotation: Quat::from_rotation_y(rotation),
                 ..default()
             },
             ..default()
         })
+        .insert(Fence {})
         .insert(RigidBody::Static)
         .insert(CollisionShape::Cuboid {
             half_extends: Vec3 {
@@ -83,11 +173,11 @@ fn spawn_fence(
         .insert(
             CollisionLayers::none()
                 .with_group(GameLayer::World)
-                .with_masks(&[GameLayer::Combine, GameLayer::Truck]),
+                .with_masks(&[GameLayer::Combine, GameLayer::Truck, GameLayer::Animal]),
         );
 }
 
-fn create_fences(
+pub fn create_fences(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     field_position_x: f32,
@@ -152,63 +242,37 @@ fn create_fences(
 fn create_field(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    field_position_x: f32,
-    field_position_z: f32,
-    field_half_size_x: f32,
-    field_half_size_z: f32,
-    entrance_side: CompassPoint,
-) {
+    rng: &mut GameRng,
+    layout: &FieldLayout,
+) -> Field {
     create_fences(
         commands,
         asset_server,
-        field_position_x,
-        field_position_z,
-        field_half_size_x,
-        field_half_size_z,
-        entrance_side,
+        layout.position.x,
+        layout.position.y,
+        layout.half_size.x,
+        layout.half_size.y,
+        layout.entrance,
     );
-    let mut x = field_position_x - field_half_size_x;
-    loop {
-        let mut z = field_position_z - field_half_size_z;
-
-        let rotation_picker = Uniform::new(0, 359);
-        let mut rng = rand::thread_rng();
-        let choice = rotation_picker.sample(&mut rng) as f32;
-        loop {
-            commands
-                .spawn_bundle(SceneBundle {
-                    scene: asset_server.load("wheat.gltf#Scene0"),
-                    transform: Transform {
-                        translation: Vec3::new(x, 0.7, z).into(),
-                        rotation: Quat::from_rotation_y(choice.to_radians()),
-                        ..default()
-                    },
-                    ..default()
-                })
-                .insert(harvest::Crop { amount: 1 })
-                .insert(RigidBody::Sensor)
-                .insert(CollisionShape::Cuboid {
-                    half_extends: Vec3 {
-                        x: harvest::CORN_SIZE / 2.5,
-                        y: harvest::CORN_SIZE / 2.5,
-                        z: harvest::CORN_SIZE / 2.5,
-                    },
-                    border_radius: None,
-                })
-                .insert(
-                    CollisionLayers::none()
-                        .with_group(GameLayer::Crop)
-                        .with_masks(&[GameLayer::Combine, GameLayer::Truck]),
-                );
-            z += CORN_SIZE;
-            if z >= field_position_z + field_half_size_z {
-                break;
-            }
-        }
-        x += CORN_SIZE;
-        if x >= field_position_x + field_half_size_x {
-            break;
-        }
+    let columns = (2. * layout.half_size.x / CORN_SIZE).ceil() as usize;
+    let rows = (2. * layout.half_size.y / CORN_SIZE).ceil() as usize;
+    let crops = commands
+        .spawn_bundle(SpatialBundle::default())
+        .insert(crop_grid::CropGrid::new(
+            layout.crop,
+            layout.position - layout.half_size,
+            columns,
+            rows,
+            rng.stream(RngStream::Fields).gen(),
+        ))
+        .id();
+
+    Field {
+        position: layout.position,
+        half_size: layout.half_size,
+        entrance: layout.entrance,
+        crop: layout.crop,
+        crops,
     }
 }
 
@@ -217,9 +281,12 @@ pub fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    options: Res<Options>,
     asset_server: Res<AssetServer>,
 ) {
-    game.time_remaining = time::Duration::new(SECONDS_ON_TIMER, 0);
+    game.round_length = options.timer.unwrap_or_else(|| game.level.round_length());
+    game.time_remaining = game.round_length;
 
     commands.spawn_bundle(Camera3dBundle {
         transform: Transform::from_xyz(0., 100., -100.)
@@ -228,19 +295,20 @@ pub fn setup(
     });
 
     game.score = 0;
+    game.fields.clear();
     commands
         .spawn_bundle(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Plane {
                 size: (2 * GROUND_HALF_SIZE) as f32,
             })),
             material: materials.add(StandardMaterial {
-                base_color: Color::DARK_GREEN.into(),
+                base_color: Color::DARK_GREEN,
                 reflectance: 0.5,
                 metallic: 0.1,
                 ..default()
             }),
             transform: Transform {
-                translation: Vec3::ZERO.into(),
+                translation: Vec3::ZERO,
                 ..default()
             },
             ..default()
@@ -265,7 +333,7 @@ pub fn setup(
         commands
             .spawn_bundle(DirectionalLightBundle {
                 directional_light: DirectionalLight {
-                    illuminance: 30000.0,
+                    illuminance: SUN_ILLUMINANCE,
                     shadow_projection: OrthographicProjection {
                         left: -ORTH_PROJECTION_SIZE,
                         right: ORTH_PROJECTION_SIZE,
@@ -292,60 +360,14 @@ pub fn setup(
             })
             .id(),
     );
-    create_field(
-        &mut commands,
-        &asset_server,
-        -52.0,
-        30.0,
-        18.0,
-        18.0,
-        CompassPoint::South,
-    );
-    create_field(
-        &mut commands,
-        &asset_server,
-        -10.0,
-        30.0,
-        18.0,
-        18.0,
-        CompassPoint::South,
-    );
-    create_field(
-        &mut commands,
-        &asset_server,
-        32.0,
-        30.0,
-        18.0,
-        18.0,
-        CompassPoint::South,
-    );
-    create_field(
-        &mut commands,
-        &asset_server,
-        -52.0,
-        -30.0,
-        18.0,
-        18.0,
-        CompassPoint::North,
-    );
-    create_field(
-        &mut commands,
-        &asset_server,
-        -10.0,
-        -30.0,
-        18.0,
-        18.0,
-        CompassPoint::North,
-    );
-    create_field(
-        &mut commands,
-        &asset_server,
-        32.0,
-        -30.0,
-        18.0,
-        18.0,
-        CompassPoint::North,
-    );
+    for (index, field) in options.layout.fields.iter().enumerate() {
+        // the tutorial only needs the one
+        if game.level == Level::Tutorial && index > 0 {
+            break;
+        }
+        game.fields
+            .push(create_field(&mut commands, &asset_server, &mut rng, field));
+    }
 }
 
 pub fn update_score(
@@ -359,7 +381,7 @@ pub fn update_score(
 
 pub fn countdown_timer(
     mut game: ResMut<Game>,
-    mut time: ResMut<Time>,
+    time: Res<Time>,
     mut app_state: ResMut<State<GameState>>,
 ) {
     if game.time_remaining > std::time::Duration::ZERO {
@@ -369,7 +391,66 @@ pub fn countdown_timer(
         return;
     }
 
+    // something else may already have queued a change this frame, such as the tutorial
+    // finishing or a round being loaded, and that goes ahead instead
     if *app_state.current() != GameState::GameOver {
-        app_state.set(GameState::GameOver).unwrap();
+        let _ = app_state.set(GameState::GameOver);
+    }
+}
+
+pub fn return_to_menu(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        // otherwise the menu sees the same press and starts straight back up
+        keyboard_input.clear();
+        app_state.set(GameState::Menu).unwrap();
     }
 }
+
+// clears out everything from the last round before the next one
+pub fn cleanup(mut commands: Commands, entities: Query<Entity, Without<Parent>>) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// console: `time 30` leaves thirty seconds on the clock
+pub fn console_time(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let seconds = match args {
+        [seconds] => seconds
+            .parse::<f32>()
+            .ok()
+            .filter(|seconds| *seconds >= 0. && *seconds <= MAX_ROUND_SECONDS),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        format!(
+            "time needs a number of seconds, at most {}",
+            MAX_ROUND_SECONDS
+        )
+    })?;
+    let mut game = world.resource_mut::<Game>();
+    game.time_remaining = time::Duration::from_secs_f32(seconds);
+    // the time played so far is worked out from this, so it mustn't go below zero
+    game.round_length = game.round_length.max(game.time_remaining);
+    Ok(format!("{} seconds left", seconds))
+}
+
+// console: `state menu`, and so on
+pub fn console_state(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let state = match args {
+        ["menu"] => GameState::Menu,
+        ["shop"] => GameState::Shop,
+        ["dealership"] => GameState::Dealership,
+        ["playing"] => GameState::Playing,
+        ["gameover"] => GameState::GameOver,
+        _ => return Err("state needs menu, shop, dealership, playing or gameover".to_string()),
+    };
+    world
+        .resource_mut::<State<GameState>>()
+        .set(state.clone())
+        .map_err(|error| format!("can't switch: {:?}", error))?;
+    Ok(format!("switching to {:?}", state))
+}
@@ -0,0 +1,462 @@
+use crate::game;
+use crate::game::Level;
+use crate::replay::{Replay, ReplayMode};
+use crate::rng::GameRng;
+use crate::vehicles::{Combine, Loadout, Truck};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_FILE: &str = "ghosts.sav";
+const SAVE_VERSION: u32 = 1;
+// seconds between recorded positions; playback blends between them
+const SAMPLE_INTERVAL: f32 = 0.1;
+const GHOST_ALPHA: f32 = 0.35;
+
+// a run can only be raced on the same fields from the same seed
+type RaceKey = (u64, u64);
+
+#[derive(Clone, Copy)]
+struct GhostSample {
+    combine: Transform,
+    truck: Transform,
+    score: i32,
+}
+
+pub struct GhostRun {
+    score: i32,
+    combine_scene: String,
+    truck_scene: String,
+    // one every SAMPLE_INTERVAL from the start of the round
+    samples: Vec<GhostSample>,
+}
+
+impl GhostRun {
+    fn sample_at(&self, elapsed: f32) -> Option<GhostSample> {
+        let position = elapsed / SAMPLE_INTERVAL;
+        let index = position.floor() as usize;
+        let before = self.samples.get(index).or_else(|| self.samples.last())?;
+        let after = match self.samples.get(index + 1) {
+            Some(after) => after,
+            None => return Some(*before),
+        };
+        let blend = position.fract();
+        let mix = |a: &Transform, b: &Transform| Transform {
+            translation: a.translation.lerp(b.translation, blend),
+            rotation: a.rotation.slerp(b.rotation, blend),
+            scale: a.scale,
+        };
+        Some(GhostSample {
+            combine: mix(&before.combine, &after.combine),
+            truck: mix(&before.truck, &after.truck),
+            score: before.score,
+        })
+    }
+}
+
+// the best Harvest round for each field layout and seed, kept on disk between sessions
+#[derive(Default)]
+pub struct Ghosts {
+    best: HashMap<RaceKey, GhostRun>,
+    recording: Option<GhostRun>,
+    // whose best run is out on the field this round
+    racing: Option<RaceKey>,
+    // how far ahead (or behind) of the ghost's score we are
+    pub score_delta: Option<i32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GhostVehicle {
+    Combine,
+    Truck,
+}
+
+// no collision layers or rigid body, so it drives straight through everything
+#[derive(Component)]
+pub struct Ghost {
+    vehicle: GhostVehicle,
+}
+
+impl Ghosts {
+    // a round that's been loaded partway through can't be raced fairly
+    pub fn abandon(&mut self) {
+        self.recording = None;
+        self.racing = None;
+        self.score_delta = None;
+    }
+
+    // the best runs from earlier sessions, or none if they can't be read
+    pub fn load() -> Ghosts {
+        let mut ghosts = Ghosts::default();
+        if let Some(contents) = read_save() {
+            match runs_from_save_string(&contents) {
+                Some(best) => ghosts.best = best,
+                None => eprintln!("couldn't read the saved ghosts, starting without them"),
+            }
+        }
+        ghosts
+    }
+
+    fn save(&self) {
+        write_save(&runs_to_save_string(&self.best));
+    }
+}
+
+fn transform_words(transform: &Transform) -> String {
+    let (t, r) = (transform.translation, transform.rotation);
+    format!("{} {} {} {} {} {} {}", t.x, t.y, t.z, r.x, r.y, r.z, r.w)
+}
+
+// the rotation's normalized, as rounding in the file (or a hand edit) would skew the model;
+// None if there's no rotation to be had from it
+fn transform_from_numbers(numbers: &[f32]) -> Option<Transform> {
+    if !numbers.iter().all(|number| number.is_finite()) {
+        return None;
+    }
+    let rotation = Quat::from_xyzw(numbers[3], numbers[4], numbers[5], numbers[6]);
+    let length = rotation.length();
+    if length == 0. || !length.is_finite() {
+        return None;
+    }
+    Some(Transform {
+        translation: Vec3::new(numbers[0], numbers[1], numbers[2]),
+        rotation: rotation / length,
+        ..default()
+    })
+}
+
+// one `run` line per best run, followed by its samples
+fn runs_to_save_string(best: &HashMap<RaceKey, GhostRun>) -> String {
+    let mut lines = vec![format!("version {}", SAVE_VERSION)];
+    for ((layout, seed), run) in best.iter() {
+        lines.push(format!(
+            "run {} {} {} {} {} {}",
+            layout,
+            seed,
+            run.score,
+            run.combine_scene,
+            run.truck_scene,
+            run.samples.len()
+        ));
+        for sample in run.samples.iter() {
+            lines.push(format!(
+                "{} {} {}",
+                transform_words(&sample.combine),
+                transform_words(&sample.truck),
+                sample.score
+            ));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+fn runs_from_save_string(contents: &str) -> Option<HashMap<RaceKey, GhostRun>> {
+    let mut lines = contents.lines();
+    if lines.next()? != format!("version {}", SAVE_VERSION) {
+        return None;
+    }
+    let mut best = HashMap::default();
+    while let Some(line) = lines.next() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (layout, seed, score, combine_scene, truck_scene, count) = match words[..] {
+            ["run", layout, seed, score, combine_scene, truck_scene, count] => (
+                layout.parse().ok()?,
+                seed.parse().ok()?,
+                score.parse().ok()?,
+                combine_scene.to_string(),
+                truck_scene.to_string(),
+                count.parse::<usize>().ok()?,
+            ),
+            _ => return None,
+        };
+        let mut samples = Vec::new();
+        for _ in 0..count {
+            let numbers = lines
+                .next()?
+                .split_whitespace()
+                .map(|word| word.parse::<f32>().ok())
+                .collect::<Option<Vec<f32>>>()?;
+            if numbers.len() != 15 {
+                return None;
+            }
+            samples.push(GhostSample {
+                combine: transform_from_numbers(&numbers[0..7])?,
+                truck: transform_from_numbers(&numbers[7..14])?,
+                score: numbers[14] as i32,
+            });
+        }
+        best.insert(
+            (layout, seed),
+            GhostRun {
+                score,
+                combine_scene,
+                truck_scene,
+                samples,
+            },
+        );
+    }
+    Some(best)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_save() -> Option<String> {
+    std::fs::read_to_string(SAVE_FILE).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_save(contents: &str) {
+    if let Err(error) = std::fs::write(SAVE_FILE, contents) {
+        eprintln!("couldn't save the ghosts: {}", error);
+    }
+}
+
+// there's nowhere to put them on the web build, so ghosts only last the session there
+#[cfg(target_arch = "wasm32")]
+fn read_save() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_save(_contents: &str) {}
+
+// FNV-1a over where the fields are and what's in them; std's hasher is seeded
+// differently every run, so it won't do for something kept on disk
+fn layout_key(fields: &[game::Field]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for field in fields.iter() {
+        let words = [
+            field.position.x.to_bits(),
+            field.position.y.to_bits(),
+            field.half_size.x.to_bits(),
+            field.half_size.y.to_bits(),
+            field.entrance as u32,
+            field.crop as u32,
+        ];
+        for byte in words.iter().flat_map(|word| word.to_le_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn race_key(game: &game::Game, rng: &GameRng) -> RaceKey {
+    (layout_key(&game.fields), rng.seed())
+}
+
+fn elapsed(game: &game::Game) -> f32 {
+    (game.round_length - game.time_remaining).as_secs_f32()
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut ghosts: ResMut<Ghosts>,
+    game: Res<game::Game>,
+    rng: Res<GameRng>,
+    replay: Res<Replay>,
+    loadout: Loadout,
+    asset_server: Res<AssetServer>,
+) {
+    ghosts.recording = None;
+    ghosts.racing = None;
+    ghosts.score_delta = None;
+    if game.level != Level::Harvest {
+        return;
+    }
+
+    // a replay would only ever tie with itself
+    if replay.mode != ReplayMode::Playing {
+        let (combine_model, truck_model) = loadout.models(game.level);
+        ghosts.recording = Some(GhostRun {
+            score: 0,
+            combine_scene: combine_model.scene.clone(),
+            truck_scene: truck_model.scene.clone(),
+            samples: Vec::new(),
+        });
+    }
+
+    let key = race_key(&game, &rng);
+    if let Some(best) = ghosts.best.get(&key) {
+        let start = best.samples.first();
+        for (scene, vehicle, transform) in [
+            (
+                &best.combine_scene,
+                GhostVehicle::Combine,
+                start.map(|sample| sample.combine),
+            ),
+            (
+                &best.truck_scene,
+                GhostVehicle::Truck,
+                start.map(|sample| sample.truck),
+            ),
+        ] {
+            commands
+                .spawn_bundle(SceneBundle {
+                    scene: asset_server.load(scene),
+                    transform: transform.unwrap_or_default(),
+                    ..default()
+                })
+                .insert(Ghost { vehicle });
+        }
+        ghosts.racing = Some(key);
+    }
+}
+
+pub fn record_ghost(
+    mut ghosts: ResMut<Ghosts>,
+    game: Res<game::Game>,
+    combines: Query<&Transform, With<Combine>>,
+    trucks: Query<&Transform, With<Truck>>,
+) {
+    let recording = match ghosts.recording.as_mut() {
+        Some(recording) => recording,
+        None => return,
+    };
+    if let (Ok(combine), Ok(truck)) = (combines.get_single(), trucks.get_single()) {
+        // catch up to the clock, in case a frame took longer than a sample
+        while recording.samples.len() as f32 * SAMPLE_INTERVAL <= elapsed(&game) {
+            recording.samples.push(GhostSample {
+                combine: *combine,
+                truck: *truck,
+                score: game.score,
+            });
+        }
+    }
+}
+
+pub fn move_ghosts(
+    mut ghosts: ResMut<Ghosts>,
+    game: Res<game::Game>,
+    mut query: Query<(&Ghost, &mut Transform)>,
+) {
+    let sample = ghosts
+        .racing
+        .and_then(|key| ghosts.best.get(&key))
+        .and_then(|best| best.sample_at(elapsed(&game)));
+    let sample = match sample {
+        Some(sample) => sample,
+        None => return,
+    };
+    for (ghost, mut transform) in query.iter_mut() {
+        *transform = match ghost.vehicle {
+            GhostVehicle::Combine => sample.combine,
+            GhostVehicle::Truck => sample.truck,
+        };
+    }
+    ghosts.score_delta = Some(game.score - sample.score);
+}
+
+// once the race has been called off there's nothing left for them to follow
+pub fn clear_abandoned_ghosts(
+    mut commands: Commands,
+    ghosts: Res<Ghosts>,
+    query: Query<Entity, With<Ghost>>,
+) {
+    if ghosts.racing.is_some() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// the glTF materials are shared with the real vehicles, so ghosts get see-through copies
+pub fn fade_ghosts(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut faded: Local<HashMap<Handle<StandardMaterial>, Handle<StandardMaterial>>>,
+    mut meshes: Query<(Entity, &mut Handle<StandardMaterial>), Added<Handle<StandardMaterial>>>,
+    parents: Query<&Parent>,
+    ghosts: Query<(), With<Ghost>>,
+) {
+    for (entity, mut material) in meshes.iter_mut() {
+        let mut ancestor = entity;
+        while ghosts.get(ancestor).is_err() {
+            match parents.get(ancestor) {
+                Ok(parent) => ancestor = parent.get(),
+                Err(_) => break,
+            }
+        }
+        if ghosts.get(ancestor).is_err() {
+            continue;
+        }
+
+        if !faded.contains_key(&*material) {
+            let mut ghostly = match materials.get(&*material) {
+                Some(original) => original.clone(),
+                None => continue,
+            };
+            ghostly.base_color.set_a(GHOST_ALPHA);
+            ghostly.alpha_mode = AlphaMode::Blend;
+            faded.insert(material.clone(), materials.add(ghostly));
+        }
+        *material = faded[&*material].clone();
+    }
+}
+
+// keeps this round, on disk too, if it beat the best on its fields and seed
+pub fn finish_ghost(mut ghosts: ResMut<Ghosts>, game: Res<game::Game>, rng: Res<GameRng>) {
+    if let Some(mut run) = ghosts.recording.take() {
+        run.score = game.score;
+        let key = race_key(&game, &rng);
+        if ghosts
+            .best
+            .get(&key)
+            .is_none_or(|best| run.score > best.score)
+        {
+            ghosts.best.insert(key, run);
+            ghosts.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_runs_survive_a_save_and_load() {
+        let sample = GhostSample {
+            combine: Transform::from_xyz(60., 1.6, 0.).with_rotation(Quat::from_rotation_y(0.5)),
+            truck: Transform::from_xyz(70., 1., -3.5),
+            score: 120,
+        };
+        let mut best = HashMap::default();
+        best.insert(
+            (7, 42),
+            GhostRun {
+                score: 900,
+                combine_scene: "combine.gltf#Scene0".to_string(),
+                truck_scene: "truck.gltf#Scene0".to_string(),
+                samples: vec![sample, sample],
+            },
+        );
+
+        let loaded = runs_from_save_string(&runs_to_save_string(&best)).unwrap();
+        let run = &loaded[&(7, 42)];
+        assert_eq!(run.score, 900);
+        assert_eq!(run.truck_scene, "truck.gltf#Scene0");
+        assert_eq!(run.samples.len(), 2);
+        assert_eq!(run.samples[1].combine, sample.combine);
+        assert_eq!(run.samples[1].score, 120);
+    }
+
+    #[test]
+    fn rotations_are_normalized_and_bad_ones_rejected() {
+        let run = "version 1\nrun 7 42 900 combine.gltf#Scene0 truck.gltf#Scene0 1\n";
+        let sample =
+            |rotation: &str| format!("{}60 1.6 0 {} 70 1 -3.5 0 0 0 1 120\n", run, rotation);
+        let loaded = runs_from_save_string(&sample("0 0.5 0 0.5")).unwrap();
+        let rotation = loaded[&(7, 42)].samples[0].combine.rotation;
+        assert!((rotation.length() - 1.).abs() < 1e-5);
+        assert!(runs_from_save_string(&sample("0 0 0 0")).is_none());
+        assert!(runs_from_save_string(&sample("0 NaN 0 1")).is_none());
+        assert!(runs_from_save_string(&sample("0 inf 0 1")).is_none());
+    }
+
+    #[test]
+    fn a_cut_off_save_is_rejected() {
+        let saved = "version 1\nrun 7 42 900 combine.gltf#Scene0 truck.gltf#Scene0 2\n";
+        assert!(runs_from_save_string(saved).is_none());
+    }
+}
@@ -1,66 +1,171 @@
-use crate::game::ScoreChangeEvent;
-use crate::{game, vehicles};
+use crate::vehicles;
 use bevy::prelude::*;
 
-#[derive(Component)]
-pub struct Crop {
-    pub amount: i32,
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CropType {
+    Wheat,
+    Barley,
+    Corn,
+    Canola,
+}
+
+pub const CROP_TYPES: [CropType; 4] = [
+    CropType::Wheat,
+    CropType::Barley,
+    CropType::Corn,
+    CropType::Canola,
+];
+
+pub struct CropTypeInfo {
+    pub name: &'static str,
+    pub model: &'static str,
+    // for maps and menus
+    pub color: Color,
+    // grain units per plant
+    pub crop_yield: i32,
+    // score per grain unit delivered
+    pub value: i32,
+    // how much cutting it bogs the combine down
+    pub harvest_difficulty: f32,
+}
+
+// indexed by CropType
+const CROP_REGISTRY: [CropTypeInfo; 4] = [
+    CropTypeInfo {
+        name: "Wheat",
+        model: "wheat.gltf",
+        color: Color::rgb(0.9, 0.8, 0.3),
+        crop_yield: 1,
+        value: 3,
+        harvest_difficulty: 0.,
+    },
+    CropTypeInfo {
+        name: "Barley",
+        model: "barley.gltf",
+        color: Color::rgb(0.75, 0.7, 0.45),
+        crop_yield: 2,
+        value: 1,
+        harvest_difficulty: 0.1,
+    },
+    CropTypeInfo {
+        name: "Corn",
+        model: "corn.gltf",
+        color: Color::rgb(0.4, 0.65, 0.2),
+        crop_yield: 3,
+        value: 2,
+        harvest_difficulty: 0.8,
+    },
+    CropTypeInfo {
+        name: "Canola",
+        model: "canola.gltf",
+        color: Color::rgb(0.95, 0.9, 0.1),
+        crop_yield: 1,
+        value: 5,
+        harvest_difficulty: 0.4,
+    },
+];
+
+impl CropType {
+    pub fn info(&self) -> &'static CropTypeInfo {
+        &CROP_REGISTRY[*self as usize]
+    }
+
+    // matches the registry name, in any case
+    pub fn from_name(name: &str) -> Option<CropType> {
+        CROP_TYPES
+            .into_iter()
+            .find(|crop| crop.info().name.eq_ignore_ascii_case(name))
+    }
+}
+
+// a mixed load of grain, as carried by the combine and truck
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct CropLoad {
+    amounts: [i32; 4],
+}
+
+impl CropLoad {
+    pub fn total(&self) -> i32 {
+        self.amounts.iter().sum()
+    }
+
+    pub fn amount(&self, crop: CropType) -> i32 {
+        self.amounts[crop as usize]
+    }
+
+    pub fn value(&self) -> i32 {
+        CROP_TYPES
+            .iter()
+            .map(|crop| self.amount(*crop) * crop.info().value)
+            .sum()
+    }
+
+    pub fn add(&mut self, crop: CropType, amount: i32) {
+        self.amounts[crop as usize] += amount;
+    }
+
+    pub fn add_load(&mut self, other: &CropLoad) {
+        for crop in CROP_TYPES {
+            self.add(crop, other.amount(crop));
+        }
+    }
+
+    // takes `amount` units in proportion to what's here
+    pub fn take(&mut self, amount: i32) -> CropLoad {
+        let total = self.total();
+        let amount = amount.min(total);
+        let mut taken = CropLoad::default();
+        if amount <= 0 {
+            return taken;
+        }
+        for crop in CROP_TYPES {
+            taken.add(crop, self.amount(crop) * amount / total);
+        }
+        // whatever rounding left behind
+        for crop in CROP_TYPES {
+            let short = (amount - taken.total()).min(self.amount(crop) - taken.amount(crop));
+            taken.add(crop, short);
+        }
+        for crop in CROP_TYPES {
+            self.amounts[crop as usize] -= taken.amount(crop);
+        }
+        taken
+    }
+
+    pub fn clear(&mut self) {
+        self.amounts = [0; 4];
+    }
 }
 
 pub const CORN_SIZE: f32 = 2.0;
-pub const CORN_SIZE_FILL_FRACTION: f32 = 0.98;
 
 pub struct CropHarvestedEvent {
-    pub entity: Entity,
+    pub position: Vec3,
+    pub crop_type: CropType,
+    pub amount: i32,
 }
 
 pub struct CropSquashedEvent {
-    pub entity: Entity,
+    pub position: Vec3,
 }
 
 pub fn crop_events_handler(
-    mut commands: Commands,
-    mut crop_squashed_events: EventReader<CropSquashedEvent>,
     mut crop_harvested_events: EventReader<CropHarvestedEvent>,
-    mut query: Query<(&Crop, &Transform)>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut combine_store: ResMut<vehicles::CombineStorage>,
-    asset_server: Res<AssetServer>,
+    mut combines: Query<&mut vehicles::Combine>,
 ) {
-    for event in crop_squashed_events.iter() {
-        let entity = event.entity;
-        if let Ok((crop, transform)) = query.get_mut(entity) {
-            let position = transform.translation;
-            commands.entity(event.entity).despawn_recursive();
-            commands.spawn_bundle(SceneBundle {
-                scene: asset_server.load("squashed_wheat.gltf#Scene0"),
-                transform: Transform {
-                    translation: Vec3::new(transform.translation.x, 0.1, transform.translation.z)
-                        .into(),
-                    ..default()
-                },
-                ..default()
-            });
-        }
-    }
-
     for event in crop_harvested_events.iter() {
-        let entity = event.entity;
-        if let Ok((crop, transform)) = query.get_mut(entity) {
-            if combine_store.contents < combine_store.capacity {
-                combine_store.contents += crop.amount;
-            }
-            commands.entity(entity).despawn_recursive();
-            commands.spawn_bundle(SceneBundle {
-                scene: asset_server.load("harvested_wheat.gltf#Scene0"),
-                transform: Transform {
-                    translation: Vec3::new(transform.translation.x, 0.1, transform.translation.z)
-                        .into(),
-                    ..default()
-                },
-                ..default()
-            });
-        };
+        let space = combine_store.capacity - combine_store.contents.total();
+        if space > 0 {
+            combine_store
+                .contents
+                .add(event.crop_type, i32::min(event.amount, space));
+        }
+        for mut combine in combines.iter_mut() {
+            combine.vehicle.drag = combine
+                .vehicle
+                .drag
+                .max(event.crop_type.info().harvest_difficulty);
+        }
     }
 }
@@ -0,0 +1,184 @@
+use crate::crop_grid::{CropCutter, PlantState};
+use crate::game;
+use crate::game::GameLayer;
+use crate::vehicles::Combine;
+
+use bevy::prelude::*;
+use heron::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum HeaderKind {
+    Narrow,
+    #[default]
+    Standard,
+    Wide,
+}
+
+pub const HEADER_KINDS: [HeaderKind; 3] =
+    [HeaderKind::Narrow, HeaderKind::Standard, HeaderKind::Wide];
+
+impl HeaderKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HeaderKind::Narrow => "narrow",
+            HeaderKind::Standard => "standard",
+            HeaderKind::Wide => "wide",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HeaderKind> {
+        HEADER_KINDS.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn cutting_width(&self) -> f32 {
+        match self {
+            HeaderKind::Narrow => 4.,
+            HeaderKind::Standard => 8.,
+            // wider than a gateway, so it has to be raised to get in and out of fields
+            HeaderKind::Wide => 14.,
+        }
+    }
+
+    pub fn turn_penalty(&self) -> f32 {
+        match self {
+            HeaderKind::Narrow => 0.,
+            HeaderKind::Standard => 0.25,
+            HeaderKind::Wide => 0.6,
+        }
+    }
+
+    fn next(&self) -> HeaderKind {
+        match self {
+            HeaderKind::Narrow => HeaderKind::Standard,
+            HeaderKind::Standard => HeaderKind::Wide,
+            HeaderKind::Wide => HeaderKind::Narrow,
+        }
+    }
+}
+
+// the header last swapped on with H, so the combine comes back with it next round
+#[derive(Default)]
+pub struct HeaderChoice {
+    pub kind: HeaderKind,
+}
+
+#[derive(Component)]
+pub struct Header {
+    pub kind: HeaderKind,
+    pub raised: bool,
+    pub combine: Entity,
+}
+
+// relative to the combine, which faces -z
+const LOWERED_POSITION: Vec3 = Vec3::new(0., -1.0, -2.6);
+const RAISED_POSITION: Vec3 = Vec3::new(0., 0.2, -2.4);
+const HEADER_DEPTH: f32 = 1.;
+
+pub fn spawn_header(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    combine: Entity,
+    kind: HeaderKind,
+) {
+    let width = kind.cutting_width();
+    let header = commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::new(width, 0.4, HEADER_DEPTH))),
+            material: materials.add(Color::rgb(0.8, 0.7, 0.1).into()),
+            transform: Transform::from_translation(LOWERED_POSITION),
+            ..default()
+        })
+        .insert(Header {
+            kind,
+            raised: false,
+            combine,
+        })
+        .insert(CropCutter {
+            half_size: Vec2::new(width / 2., HEADER_DEPTH / 2.),
+            offset: 0.,
+            action: PlantState::Harvested,
+            enabled: true,
+        })
+        .insert(RigidBody::Sensor)
+        .insert(CollisionShape::Cuboid {
+            half_extends: Vec3 {
+                x: width / 2.,
+                y: 0.3,
+                z: HEADER_DEPTH / 2.,
+            },
+            border_radius: None,
+        })
+        .insert(
+            CollisionLayers::none()
+                .with_group(GameLayer::Combine)
+                .with_masks(&[GameLayer::World]),
+        )
+        .insert(Collisions::default())
+        .id();
+    commands.entity(combine).add_child(header);
+}
+
+pub fn set_raised(
+    header: &mut Header,
+    cutter: &mut CropCutter,
+    transform: &mut Transform,
+    raised: bool,
+) {
+    header.raised = raised;
+    cutter.enabled = !raised;
+    transform.translation = if raised {
+        RAISED_POSITION
+    } else {
+        LOWERED_POSITION
+    };
+}
+
+pub fn change_header(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut headers: Query<(Entity, &mut Header, &mut CropCutter, &mut Transform)>,
+    mut combines: Query<&mut Combine>,
+    mut choice: ResMut<HeaderChoice>,
+) {
+    for (entity, mut header, mut cutter, mut transform) in headers.iter_mut() {
+        if keyboard_input.just_pressed(KeyCode::R) {
+            let raised = !header.raised;
+            set_raised(&mut header, &mut cutter, &mut transform, raised);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::H) {
+            let kind = header.kind.next();
+            choice.kind = kind;
+            commands.entity(entity).despawn_recursive();
+            spawn_header(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                header.combine,
+                kind,
+            );
+            if let Ok(mut combine) = combines.get_mut(header.combine) {
+                combine.vehicle.turn_penalty = kind.turn_penalty();
+            }
+        }
+    }
+}
+
+// A lowered header won't go through a fence, so any fence it touches holds the combine
+// up until it's raised or backed away.
+pub fn header_clearance(
+    headers: Query<(&Header, &Collisions)>,
+    fences: Query<(), With<game::Fence>>,
+    mut combines: Query<&mut Combine>,
+) {
+    for (header, collisions) in headers.iter() {
+        let touching_fence =
+            !header.raised && collisions.entities().any(|entity| fences.contains(entity));
+        if let Ok(mut combine) = combines.get_mut(header.combine) {
+            combine.vehicle.blocked_ahead = touching_fence;
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::animals::{Animal, Herder};
+use crate::game;
+use crate::game::{GameLayer, ScoreChangeEvent};
+use crate::minimap;
+use crate::vehicles;
+
+use bevy::prelude::*;
+use heron::prelude::*;
+
+#[derive(Component)]
+pub struct Pen {
+    pub animals_inside: usize,
+}
+
+#[derive(Component)]
+pub struct Dog {
+    pub vehicle: vehicles::Vehicle,
+}
+
+#[derive(Default)]
+pub struct HerdingObjective {
+    // switched from the menu or with --herding; the tutorial leaves it out either way
+    pub enabled: bool,
+    pub completed: bool,
+}
+
+const PEN_X: f32 = -95.;
+const PEN_Z: f32 = 0.;
+const PEN_HALF_SIZE: f32 = 8.;
+pub const HERDING_BONUS: i32 = 250;
+
+pub fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut objective: ResMut<HerdingObjective>,
+    game: Res<game::Game>,
+    asset_server: Res<AssetServer>,
+) {
+    objective.completed = false;
+    if !objective.enabled || !game.level.has_herding() {
+        return;
+    }
+
+    // the gate faces back towards the fields
+    game::create_fences(
+        &mut commands,
+        &asset_server,
+        PEN_X,
+        PEN_Z,
+        PEN_HALF_SIZE,
+        PEN_HALF_SIZE,
+        game::CompassPoint::West,
+    );
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Plane {
+                size: 2. * PEN_HALF_SIZE,
+            })),
+            material: materials.add(Color::rgba(0.5, 0.35, 0.2, 0.5).into()),
+            transform: Transform {
+                translation: Vec3::new(PEN_X, 0.05, PEN_Z),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Pen { animals_inside: 0 })
+        .insert(RigidBody::Sensor)
+        .insert(CollisionShape::Cuboid {
+            half_extends: Vec3 {
+                x: PEN_HALF_SIZE,
+                y: 2.,
+                z: PEN_HALF_SIZE,
+            },
+            border_radius: None,
+        })
+        .insert(
+            CollisionLayers::none()
+                .with_group(GameLayer::World)
+                .with_masks(&[GameLayer::Animal]),
+        )
+        .insert(Collisions::default());
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 0.8 })),
+            material: materials.add(Color::rgba(0.3, 0.2, 0.1, 1.).into()),
+            transform: Transform {
+                translation: Vec3::new(PEN_X + 25., 0.5, PEN_Z),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Dog {
+            vehicle: vehicles::Vehicle {
+                drive_speed: 12.,
+                turn_rate: 6.,
+                acceleration: 30.,
+                max_reverse: 4.,
+                wheelbase: 0.6,
+                max_steering_angle: 0.8,
+                lateral_grip: 12.,
+                braking: 40.,
+                ..default()
+            },
+        })
+        .insert(Herder {})
+        .insert(minimap::MinimapIcon {
+            color: Color::rgb(0.5, 0.3, 0.1),
+            size: 5.,
+        })
+        .insert(
+            CollisionLayers::none()
+                .with_groups(&[GameLayer::Animal, GameLayer::Obstacle])
+                .with_masks(&[GameLayer::World, GameLayer::Obstacle, GameLayer::Vehicle]),
+        )
+        .insert(RigidBody::Dynamic)
+        .insert(RotationConstraints::restrict_to_y_only())
+        .insert(Velocity::from_linear(Vec3::ZERO).with_angular(AxisAngle::new(Vec3::Y, 0.)))
+        .insert(CollisionShape::Cuboid {
+            half_extends: Vec3 {
+                x: 0.4,
+                y: 0.4,
+                z: 0.4,
+            },
+            border_radius: Some(0.1),
+        });
+}
+
+pub fn move_dog(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<(&mut Dog, &Transform, &mut Velocity)>,
+    time: Res<Time>,
+) {
+    if let Ok((mut dog, transform, mut velocity)) = query.get_single_mut() {
+        let mut requested_direction = Vec2::new(0., 0.);
+        if keyboard_input.pressed(KeyCode::I) {
+            requested_direction.y += 1.;
+        }
+        if keyboard_input.pressed(KeyCode::J) {
+            requested_direction.x += 1.;
+        }
+        if keyboard_input.pressed(KeyCode::K) {
+            requested_direction.y -= 1.;
+        }
+        if keyboard_input.pressed(KeyCode::L) {
+            requested_direction.x -= 1.;
+        }
+        vehicles::update_vehicle(
+            &mut dog.vehicle,
+            requested_direction,
+            transform,
+            &mut velocity,
+            &time,
+        );
+    }
+}
+
+pub fn pen_update(mut pens: Query<(&mut Pen, &Collisions)>, animals: Query<&Animal>) {
+    for (mut pen, collisions) in pens.iter_mut() {
+        pen.animals_inside = collisions
+            .entities()
+            .filter(|entity| animals.get(*entity).is_ok())
+            .count();
+    }
+}
+
+pub fn herding_check(
+    mut objective: ResMut<HerdingObjective>,
+    pens: Query<&Pen>,
+    animals: Query<&Animal>,
+    mut score_event: EventWriter<ScoreChangeEvent>,
+) {
+    if !objective.enabled || objective.completed {
+        return;
+    }
+
+    let total_animals = animals.iter().count();
+    if let Ok(pen) = pens.get_single() {
+        if total_animals > 0 && pen.animals_inside == total_animals {
+            score_event.send(ScoreChangeEvent {
+                amount: HERDING_BONUS,
+            });
+            objective.completed = true;
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::game::{CompassPoint, GROUND_HALF_SIZE};
+use crate::harvest::CropType;
+
+use bevy::prelude::*;
+
+const DEFAULT_LAYOUT: &str = include_str!("../assets/farm.level");
+
+#[derive(Clone)]
+pub struct FieldLayout {
+    pub position: Vec2,
+    pub half_size: Vec2,
+    pub entrance: CompassPoint,
+    pub crop: CropType,
+}
+
+// where the fields go, from assets/farm.level unless --level names another file
+#[derive(Clone)]
+pub struct MapLayout {
+    pub fields: Vec<FieldLayout>,
+}
+
+impl Default for MapLayout {
+    fn default() -> Self {
+        match parse_layout(DEFAULT_LAYOUT) {
+            Ok(layout) => layout,
+            Err(error) => panic!("assets/farm.level: {}", error),
+        }
+    }
+}
+
+impl MapLayout {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: &str) -> Result<MapLayout, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        parse_layout(&contents).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    // there's no file system to read one from
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_file(path: &str) -> Result<MapLayout, String> {
+        Err(format!(
+            "{}: level files can't be loaded in the browser",
+            path
+        ))
+    }
+}
+
+fn parse_compass_point(name: &str) -> Option<CompassPoint> {
+    match name {
+        "north" => Some(CompassPoint::North),
+        "east" => Some(CompassPoint::East),
+        "south" => Some(CompassPoint::South),
+        "west" => Some(CompassPoint::West),
+        _ => None,
+    }
+}
+
+fn parse_layout(contents: &str) -> Result<MapLayout, String> {
+    let mut fields = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |problem: &str| format!("line {}: {}", number + 1, problem);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words[0] != "field" {
+            return Err(error(&format!("unknown entry {}", words[0])));
+        }
+        if words.len() != 7 {
+            return Err(error(
+                "a field needs x, z, half width, half depth, gate side and crop",
+            ));
+        }
+        // NaN would slip through the size and edge checks below, as min and max skip it
+        let numbers = words[1..5]
+            .iter()
+            .map(|word| word.parse::<f32>().ok().filter(|n| n.is_finite()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| error("the position and size must be numbers"))?;
+        let field = FieldLayout {
+            position: Vec2::new(numbers[0], numbers[1]),
+            half_size: Vec2::new(numbers[2], numbers[3]),
+            entrance: parse_compass_point(words[5])
+                .ok_or_else(|| error(&format!("{} isn't a gate side", words[5])))?,
+            crop: CropType::from_name(words[6])
+                .ok_or_else(|| error(&format!("{} isn't a crop", words[6])))?,
+        };
+        if field.half_size.min_element() <= 0. {
+            return Err(error("the field has no size"));
+        }
+        let far_corner = field.position.abs() + field.half_size;
+        if far_corner.max_element() > GROUND_HALF_SIZE as f32 {
+            return Err(error("the field runs off the edge of the map"));
+        }
+        fields.push(field);
+    }
+    if fields.is_empty() {
+        return Err("there are no fields".to_string());
+    }
+    Ok(MapLayout { fields })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_layout_parses() {
+        assert!(!MapLayout::default().fields.is_empty());
+    }
+
+    #[test]
+    fn field_off_the_map() {
+        let error = parse_layout("# too far east\nfield 10000 0 20 20 west wheat")
+            .err()
+            .unwrap();
+        assert_eq!(error, "line 2: the field runs off the edge of the map");
+    }
+
+    #[test]
+    fn numbers_must_be_finite() {
+        for line in [
+            "field NaN 0 20 20 west wheat",
+            "field 0 0 NaN 20 west wheat",
+            "field inf 0 20 20 west wheat",
+            "field 0 0 20 -inf west wheat",
+        ] {
+            let error = parse_layout(line).err().unwrap();
+            assert_eq!(error, "line 1: the position and size must be numbers");
+        }
+    }
+
+    #[test]
+    fn unknown_crop() {
+        let error = parse_layout("field 0 0 20 20 west turnips").err().unwrap();
+        assert_eq!(error, "line 1: turnips isn't a crop");
+    }
+}
@@ -1,48 +1,195 @@
 mod animals;
+mod autopilot;
+mod career;
+mod console;
+mod contracts;
+mod crop_grid;
+mod daylight;
+mod dealership;
 mod drop_zone;
 mod game;
+mod ghost;
 mod harvest;
+mod header;
+mod herding;
+mod layout;
+mod menu;
+mod minimap;
+mod navigation;
+mod options;
+mod particles;
+mod replay;
+mod rng;
+mod savegame;
+mod shop;
+mod sound;
+mod tutorial;
 mod ui;
 mod vehicles;
+mod weather;
 
+use bevy::input::InputSystem;
 use bevy::prelude::*;
+use console::ConsoleApp;
 use heron::prelude::*;
 
 fn main() {
-    App::new()
-        .init_resource::<game::Game>()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(PhysicsPlugin::default())
+    let options = options::Options::from_environment();
+    let mut app = App::new();
+    app.init_resource::<game::Game>()
+        .init_resource::<autopilot::TruckAutopilot>()
+        .init_resource::<autopilot::CombineAutopilot>()
+        .init_resource::<console::Console>()
+        .init_resource::<contracts::Contracts>()
+        .init_resource::<replay::Replay>()
+        .init_resource::<savegame::PendingRound>()
+        .init_resource::<dealership::VehicleCatalogue>()
+        .init_resource::<dealership::VehicleChoice>()
+        .init_resource::<header::HeaderChoice>()
+        .init_resource::<navigation::NavGrid>()
+        .init_resource::<navigation::RoutePreview>()
+        .init_resource::<crop_grid::PlantModels>()
+        .init_resource::<weather::Weather>()
+        .init_resource::<daylight::TimeOfDay>()
+        .init_resource::<sound::SoundSettings>()
+        .init_resource::<particles::ParticleAssets>()
+        .init_resource::<tutorial::Tutorial>()
+        .init_resource::<ui::Notice>()
+        .init_resource::<weather::RainAssets>();
+    options.add_default_plugins(&mut app);
+    app.add_plugin(PhysicsPlugin::default())
+        .init_resource::<sound::Sounds>()
         .insert_resource(Gravity::from(Vec3::new(0.0, -9.81, 0.0)))
-        .add_state(game::GameState::Playing)
+        .add_state(options.initial_state())
         .add_event::<harvest::CropHarvestedEvent>()
         .add_event::<harvest::CropSquashedEvent>()
         .add_event::<game::ScoreChangeEvent>()
+        .add_event::<vehicles::GrainTransferEvent>()
+        .add_event::<drop_zone::GrainDeliveredEvent>()
         .insert_resource(vehicles::CombineStorage {
-            capacity: 500,
-            contents: 0,
+            // set from the chosen model when the round starts
+            capacity: 0,
+            contents: harvest::CropLoad::default(),
         })
         .insert_resource(vehicles::TruckStorage {
-            capacity: 1500,
-            contents: 0,
+            capacity: 0,
+            contents: harvest::CropLoad::default(),
         })
-        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(game::setup))
+        .insert_resource(career::Career::load())
+        .insert_resource(ghost::Ghosts::load())
+        .insert_resource(rng::GameRng::new(options.seed))
+        .insert_resource(options)
+        .add_startup_system(options::apply_start)
+        .init_resource::<herding::HerdingObjective>()
+        .add_system_set(SystemSet::on_enter(game::GameState::Menu).with_system(menu::setup))
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Menu)
+                .with_system(menu::menu_input)
+                .with_system(menu::toggle_herding),
+        )
+        .add_system_set(SystemSet::on_exit(game::GameState::Menu).with_system(menu::teardown))
+        .add_system_set(
+            SystemSet::on_enter(game::GameState::Dealership).with_system(dealership::setup),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Dealership)
+                .with_system(dealership::dealership_input),
+        )
+        .add_system_set(
+            SystemSet::on_exit(game::GameState::Dealership).with_system(dealership::teardown),
+        )
+        .add_system_set(SystemSet::on_enter(game::GameState::Shop).with_system(shop::setup))
+        .add_system_set(SystemSet::on_update(game::GameState::Shop).with_system(shop::shop_input))
+        .add_system_set(SystemSet::on_exit(game::GameState::Shop).with_system(shop::teardown))
+        .add_system_to_stage(CoreStage::First, replay::tick_clock)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            console::console_input
+                .label(console::ConsoleInput)
+                .after(InputSystem),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            replay::record_or_play_input
+                .after(InputSystem)
+                .after(console::ConsoleInput),
+        )
+        .add_system(console::run_commands.exclusive_system())
+        .add_system(console::draw_console)
+        .add_console_command("time", "<seconds>", game::console_time)
+        .add_console_command(
+            "state",
+            "<menu|shop|dealership|playing|gameover>",
+            game::console_state,
+        )
+        .add_console_command("fill", "[crop]", vehicles::console_fill)
+        .add_console_command(
+            "teleport",
+            "<combine|truck> <x> <z>",
+            vehicles::console_teleport,
+        )
+        .add_console_command("spawn", "<sheep|pig> [count]", animals::console_spawn)
+        .add_console_command("regrow", "[field number]", crop_grid::console_regrow)
+        .add_system_set(
+            SystemSet::on_enter(game::GameState::Playing)
+                .with_system(replay::start_round.label(replay::RoundStart))
+                .with_system(replay::setup_viewer.after(replay::RoundStart)),
+        )
+        .add_system_set(
+            SystemSet::on_enter(game::GameState::Playing)
+                .with_system(game::setup.after(replay::RoundStart))
+                .with_system(ghost::setup.after(replay::RoundStart)),
+        )
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(autopilot::setup))
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(tutorial::setup))
         .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(vehicles::setup))
         .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(drop_zone::setup))
         .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(ui::setup))
         .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(animals::setup))
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(herding::setup))
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(weather::setup))
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(daylight::setup))
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(minimap::setup))
+        .add_system_set(SystemSet::on_enter(game::GameState::Playing).with_system(contracts::setup))
         .add_system_set(
             SystemSet::on_update(game::GameState::Playing)
                 .with_system(vehicles::move_combine)
                 .with_system(vehicles::move_truck)
-                .with_system(vehicles::combine_collision_check)
-                .with_system(vehicles::truck_collision_check)
                 .with_system(vehicles::transfer_harvest),
         )
         .add_system_set(
             SystemSet::on_update(game::GameState::Playing)
+                .with_system(header::change_header)
+                .with_system(header::header_clearance),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(autopilot::toggle_truck_autopilot)
+                .with_system(autopilot::drive_truck)
+                .with_system(autopilot::toggle_combine_autopilot)
+                .with_system(autopilot::drive_combine),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(navigation::build_nav_grid)
+                .with_system(navigation::toggle_route_preview)
+                .with_system(navigation::update_route_preview),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(career::restore_fields)
+                .with_system(crop_grid::cut_crops)
+                .with_system(crop_grid::load_plant_models)
+                .with_system(crop_grid::rebuild_crop_meshes)
                 .with_system(harvest::crop_events_handler),
         )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(savegame::save_round)
+                .with_system(savegame::load_round)
+                // after the career's fields, or they'd go over the saved ones
+                .with_system(savegame::restore_round.after(career::restore_fields)),
+        )
         .add_system_set(
             SystemSet::on_update(game::GameState::Playing)
                 .with_system(drop_zone::drop_zone_update)
@@ -51,11 +198,74 @@ fn main() {
         .add_system_set(
             SystemSet::on_update(game::GameState::Playing).with_system(animals::move_animals),
         )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(herding::move_dog)
+                .with_system(herding::pen_update)
+                .with_system(herding::herding_check),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(contracts::offer_contracts)
+                .with_system(contracts::track_contracts),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(weather::update_weather)
+                .with_system(weather::soak_vehicles)
+                .with_system(weather::update_rain),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(daylight::update_time_of_day)
+                .with_system(daylight::update_sun)
+                .with_system(daylight::update_headlights),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(sound::engine_sounds)
+                .with_system(sound::crop_sounds)
+                .with_system(sound::animal_sounds)
+                .with_system(sound::score_sounds),
+        )
+        .add_system(sound::sound_controls)
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(particles::trigger_emitters)
+                .with_system(particles::update_emitters)
+                .with_system(particles::update_particles),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(minimap::draw_minimap_fields)
+                .with_system(minimap::shade_minimap_fields)
+                .with_system(minimap::update_minimap_markers),
+        )
         .add_system_set(
             SystemSet::on_update(game::GameState::Playing)
                 .with_system(ui::update_ui_score)
                 .with_system(ui::update_contents)
-                .with_system(ui::update_time),
+                .with_system(ui::update_prompt)
+                .with_system(ui::update_time)
+                .with_system(ui::update_weather_forecast)
+                .with_system(ui::update_contracts)
+                .with_system(ui::update_ghost_delta),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(tutorial::advance_tutorial)
+                .with_system(tutorial::update_tutorial_marker)
+                .with_system(tutorial::finish_tutorial),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing).with_system(replay::replay_viewer),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Playing)
+                .with_system(ghost::record_ghost)
+                .with_system(ghost::move_ghosts)
+                .with_system(ghost::clear_abandoned_ghosts)
+                .with_system(ghost::fade_ghosts),
         )
         .add_system_set(
             SystemSet::on_update(game::GameState::Playing)
@@ -63,7 +273,29 @@ fn main() {
                 .with_system(game::countdown_timer),
         )
         .add_system_set(
-            SystemSet::on_enter(game::GameState::GameOver).with_system(ui::display_final_score),
+            SystemSet::on_enter(game::GameState::GameOver)
+                .with_system(career::end_day.label(career::EndOfDay))
+                .with_system(ui::display_final_score.after(career::EndOfDay))
+                .with_system(sound::stop_engines)
+                .with_system(replay::end_round)
+                .with_system(ghost::finish_ghost)
+                .with_system(options::finish_headless.after(career::EndOfDay))
+                .with_system(minimap::teardown)
+                .with_system(tutorial::teardown),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::GameOver)
+                .with_system(game::return_to_menu)
+                .with_system(replay::watch_replay),
+        )
+        .add_system_set(SystemSet::on_exit(game::GameState::GameOver).with_system(game::cleanup))
+        .add_system_set(
+            SystemSet::on_enter(game::GameState::Rewinding)
+                .with_system(game::cleanup)
+                .with_system(sound::stop_engines),
+        )
+        .add_system_set(
+            SystemSet::on_update(game::GameState::Rewinding).with_system(replay::rewind),
         )
         .run();
 }
@@ -0,0 +1,331 @@
+use crate::career::Career;
+use crate::game;
+use crate::game::{GameState, Level};
+use crate::herding::HerdingObjective;
+use crate::savegame;
+use crate::savegame::RoundLoader;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+pub const BUTTON_COLOR: Color = Color::rgb(0.25, 0.35, 0.15);
+pub const HOVERED_COLOR: Color = Color::rgb(0.35, 0.5, 0.2);
+
+#[derive(Component)]
+pub struct MenuRoot {}
+
+#[derive(Component)]
+pub struct MenuCamera {}
+
+#[derive(Component)]
+pub struct ShopButton {}
+
+#[derive(Component)]
+pub struct ResumeButton {}
+
+#[derive(Component)]
+pub struct HerdingButton {}
+
+// says what went wrong, such as a saved round that couldn't be read
+#[derive(Component)]
+pub struct MenuMessage {}
+
+#[derive(Component)]
+pub struct LevelButton {
+    level: Level,
+    // throws away the saved career first
+    new_career: bool,
+}
+
+// the buttons marked with a `T` whose hover or click changed this frame; `F` keeps the
+// query clear of the system's other button queries, which also write the colour
+pub type ButtonChanges<'w, 's, T, F = ()> =
+    Query<'w, 's, (&'static Interaction, &'static mut UiColor), (Changed<Interaction>, With<T>, F)>;
+
+// how big a screen's buttons are, and their labels
+#[derive(Clone, Copy)]
+pub struct ButtonSize {
+    pub width: f32,
+    pub height: f32,
+    pub margin: f32,
+    pub font_size: f32,
+}
+
+const MENU_BUTTON: ButtonSize = ButtonSize {
+    width: 260.,
+    height: 60.,
+    margin: 10.,
+    font_size: 32.,
+};
+
+pub fn text_style(font: &Handle<Font>, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    }
+}
+
+// the menu, shop and dealership all share the one look
+pub fn spawn_button(
+    parent: &mut ChildBuilder,
+    font: &Handle<Font>,
+    label: &str,
+    size: ButtonSize,
+    color: Color,
+    component: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(size.width), Val::Px(size.height)),
+                margin: UiRect::all(Val::Px(size.margin)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: color.into(),
+            ..default()
+        })
+        .insert(component)
+        .with_children(|button| {
+            button.spawn_bundle(TextBundle::from_section(
+                label,
+                text_style(font, size.font_size),
+            ));
+        });
+}
+
+fn spawn_menu_button(
+    parent: &mut ChildBuilder,
+    font: &Handle<Font>,
+    label: &str,
+    component: impl Component,
+) {
+    spawn_button(parent, font, label, MENU_BUTTON, BUTTON_COLOR, component);
+}
+
+fn spawn_level_button(
+    parent: &mut ChildBuilder,
+    font: &Handle<Font>,
+    label: &str,
+    level: Level,
+    new_career: bool,
+) {
+    spawn_menu_button(parent, font, label, LevelButton { level, new_career });
+}
+
+fn herding_label(enabled: bool) -> String {
+    format!("Sheep herding: {} (H)", if enabled { "on" } else { "off" })
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    career: Res<Career>,
+    objective: Res<HerdingObjective>,
+) {
+    let font_handle = asset_server.load("fonts/abel-regular.ttf");
+
+    // the game's camera only exists while playing, but the UI still needs one
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(MenuCamera {});
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgb(0.1, 0.2, 0.1).into(),
+            ..default()
+        })
+        .insert(MenuRoot {})
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Combine Harvester",
+                text_style(&font_handle, 64.),
+            ));
+            if savegame::has_saved_round() {
+                spawn_menu_button(
+                    parent,
+                    &font_handle,
+                    "Resume saved round (L)",
+                    ResumeButton {},
+                );
+            }
+            spawn_level_button(parent, &font_handle, "Play (Enter)", Level::Harvest, false);
+            parent.spawn_bundle(TextBundle::from_section(
+                "Your best round races you as a ghost, on the same fields and seed",
+                text_style(&font_handle, 20.),
+            ));
+            spawn_level_button(
+                parent,
+                &font_handle,
+                &format!("Career: day {}, ${} (C)", career.day, career.money),
+                Level::Career,
+                false,
+            );
+            spawn_menu_button(parent, &font_handle, "Upgrade shop (U)", ShopButton {});
+            spawn_level_button(parent, &font_handle, "New career", Level::Career, true);
+            spawn_level_button(parent, &font_handle, "Tutorial (T)", Level::Tutorial, false);
+            spawn_menu_button(
+                parent,
+                &font_handle,
+                &herding_label(objective.enabled),
+                HerdingButton {},
+            );
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        color: Color::ORANGE,
+                        ..text_style(&font_handle, 24.)
+                    },
+                ))
+                .insert(MenuMessage {});
+        });
+}
+
+type MenuRoots<'w, 's> = Query<'w, 's, Entity, Or<(With<MenuRoot>, With<MenuCamera>)>>;
+
+pub fn teardown(mut commands: Commands, roots: MenuRoots) {
+    for entity in roots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// the tutorial never has it, whatever this says
+pub fn toggle_herding(
+    mut objective: ResMut<HerdingObjective>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut buttons: ButtonChanges<HerdingButton>,
+    labels: Query<&Children, With<HerdingButton>>,
+    mut texts: Query<&mut Text>,
+) {
+    let mut toggled = keyboard_input.just_pressed(KeyCode::H);
+    for (interaction, mut color) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => toggled = true,
+            Interaction::Hovered => color.0 = HOVERED_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+    if !toggled {
+        return;
+    }
+    keyboard_input.clear();
+    objective.enabled = !objective.enabled;
+    for children in labels.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = herding_label(objective.enabled);
+            }
+        }
+    }
+}
+
+// the main menu's buttons, each kept clear of the others
+#[derive(SystemParam)]
+pub struct MenuButtons<'w, 's> {
+    levels: Query<
+        'w,
+        's,
+        (
+            &'static Interaction,
+            &'static LevelButton,
+            &'static mut UiColor,
+        ),
+        Changed<Interaction>,
+    >,
+    shop: ButtonChanges<'w, 's, ShopButton, Without<LevelButton>>,
+    resume: ButtonChanges<'w, 's, ResumeButton, (Without<LevelButton>, Without<ShopButton>)>,
+}
+
+pub fn menu_input(
+    mut game: ResMut<game::Game>,
+    mut career: ResMut<Career>,
+    mut loader: RoundLoader,
+    mut app_state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut buttons: MenuButtons,
+    mut messages: Query<&mut Text, With<MenuMessage>>,
+) {
+    let mut chosen = None;
+    for (interaction, button, mut color) in buttons.levels.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                if button.new_career {
+                    *career = Career::default();
+                    career.save();
+                }
+                chosen = Some(button.level);
+            }
+            Interaction::Hovered => color.0 = HOVERED_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+    let mut shopping = keyboard_input.just_pressed(KeyCode::U);
+    for (interaction, mut color) in buttons.shop.iter_mut() {
+        match interaction {
+            Interaction::Clicked => shopping = true,
+            Interaction::Hovered => color.0 = HOVERED_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+    if shopping {
+        keyboard_input.clear();
+        app_state.set(GameState::Shop).unwrap();
+        return;
+    }
+
+    let mut resuming = keyboard_input.just_pressed(KeyCode::L);
+    for (interaction, mut color) in buttons.resume.iter_mut() {
+        match interaction {
+            Interaction::Clicked => resuming = true,
+            Interaction::Hovered => color.0 = HOVERED_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+    if resuming {
+        match loader.load(&mut game, &career) {
+            Ok(()) => {
+                keyboard_input.clear();
+                // straight back in with the same machines, rather than via the dealership
+                app_state.set(GameState::Playing).unwrap();
+                return;
+            }
+            Err(error) => {
+                keyboard_input.clear();
+                for mut text in messages.iter_mut() {
+                    text.sections[0].value = format!("Couldn't load the round: {}", error);
+                }
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        chosen = Some(Level::Harvest);
+    } else if keyboard_input.just_pressed(KeyCode::C) {
+        chosen = Some(Level::Career);
+    } else if keyboard_input.just_pressed(KeyCode::T) {
+        chosen = Some(Level::Tutorial);
+    }
+
+    if let Some(level) = chosen {
+        // T and C also toggle the autopilots, so don't let the press through
+        keyboard_input.clear();
+        game.level = level;
+        // the tutorial always uses the standard machines, so skips the dealership
+        let next = if level == Level::Tutorial {
+            GameState::Playing
+        } else {
+            GameState::Dealership
+        };
+        app_state.set(next).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+use crate::crop_grid::CropGrid;
+use crate::drop_zone::{DropZone, DROP_ZONE_SIZE};
+use crate::game;
+
+use bevy::prelude::*;
+
+// pixels on screen
+const MINIMAP_SIZE: f32 = 200.;
+// world units from the centre to the edge of the map
+const MINIMAP_RANGE: f32 = 120.;
+const GATE_WIDTH: f32 = 12.;
+const STUBBLE_COLOR: Color = Color::rgb(0.45, 0.35, 0.2);
+
+// shows up on the minimap as a square of this colour and size (in pixels)
+#[derive(Component)]
+pub struct MinimapIcon {
+    pub color: Color,
+    pub size: f32,
+}
+
+#[derive(Component)]
+pub struct MinimapPanel {}
+
+// holds the fields and drop zone, so the markers always draw on top of them
+#[derive(Component)]
+pub struct MinimapBackground {
+    fields_drawn: bool,
+}
+
+#[derive(Component)]
+pub struct MinimapMarker {
+    target: Entity,
+    size: f32,
+}
+
+#[derive(Component)]
+pub struct MinimapField {
+    crops: Entity,
+    color: Color,
+}
+
+// the camera looks up +z from the south, so +x is on the left of the screen
+fn to_minimap(position: Vec3) -> Vec2 {
+    let scale = MINIMAP_SIZE / (2. * MINIMAP_RANGE);
+    Vec2::new(
+        (MINIMAP_RANGE - position.x) * scale,
+        (MINIMAP_RANGE - position.z) * scale,
+    )
+}
+
+// a rectangle covering `half_size` world units either side of `centre`
+fn minimap_rect(centre: Vec3, half_size: Vec2, color: Color) -> NodeBundle {
+    let scale = MINIMAP_SIZE / (2. * MINIMAP_RANGE);
+    let corner = to_minimap(centre + Vec3::new(half_size.x, 0., half_size.y));
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(corner.x),
+                top: Val::Px(corner.y),
+                ..default()
+            },
+            size: Size::new(
+                Val::Px(2. * half_size.x * scale),
+                Val::Px(2. * half_size.y * scale),
+            ),
+            ..default()
+        },
+        color: color.into(),
+        ..default()
+    }
+}
+
+pub fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                overflow: Overflow::Hidden,
+                ..default()
+            },
+            color: Color::rgba(0.1, 0.3, 0.1, 0.6).into(),
+            ..default()
+        })
+        .insert(MinimapPanel {})
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(MinimapBackground {
+                    fields_drawn: false,
+                });
+        });
+}
+
+pub fn teardown(mut commands: Commands, panels: Query<Entity, With<MinimapPanel>>) {
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+// fields and the drop zone don't move, so they're only drawn once they exist
+pub fn draw_minimap_fields(
+    mut commands: Commands,
+    game: Res<game::Game>,
+    mut backgrounds: Query<(Entity, &mut MinimapBackground)>,
+    drop_zones: Query<&Transform, With<DropZone>>,
+) {
+    let (background, mut minimap) = match backgrounds.get_single_mut() {
+        Ok(background) => background,
+        Err(_) => return,
+    };
+    if minimap.fields_drawn || game.fields.is_empty() {
+        return;
+    }
+    let drop_zone = match drop_zones.get_single() {
+        Ok(drop_zone) => drop_zone,
+        Err(_) => return,
+    };
+    minimap.fields_drawn = true;
+
+    commands.entity(background).with_children(|parent| {
+        parent.spawn_bundle(minimap_rect(
+            drop_zone.translation,
+            Vec2::splat(DROP_ZONE_SIZE / 2.),
+            Color::rgba(0.8, 0.2, 0.2, 0.5),
+        ));
+        for field in game.fields.iter() {
+            let centre = Vec3::new(field.position.x, 0., field.position.y);
+            let color = field.crop.info().color;
+            parent
+                .spawn_bundle(minimap_rect(centre, field.half_size, color))
+                .insert(MinimapField {
+                    crops: field.crops,
+                    color,
+                });
+            // a dark notch where the way in is
+            let along = field.entrance.direction().abs();
+            let gate_half_size = Vec2::new(
+                if along.x > 0. { 1. } else { GATE_WIDTH / 2. },
+                if along.z > 0. { 1. } else { GATE_WIDTH / 2. },
+            );
+            parent.spawn_bundle(minimap_rect(
+                field.gate_position(0.),
+                gate_half_size,
+                Color::rgb(0.2, 0.15, 0.1),
+            ));
+        }
+    });
+}
+
+// fades each field towards stubble as it gets harvested
+pub fn shade_minimap_fields(
+    grids: Query<&CropGrid, Changed<CropGrid>>,
+    mut fields: Query<(&MinimapField, &mut UiColor)>,
+) {
+    for (field, mut ui_color) in fields.iter_mut() {
+        if let Ok(grid) = grids.get(field.crops) {
+            let harvested = grid.harvested_fraction();
+            ui_color.0 = field.color * (1. - harvested) + STUBBLE_COLOR * harvested;
+        }
+    }
+}
+
+pub fn update_minimap_markers(
+    mut commands: Commands,
+    panels: Query<Entity, With<MinimapPanel>>,
+    new_icons: Query<(Entity, &MinimapIcon), Added<MinimapIcon>>,
+    targets: Query<&Transform, With<MinimapIcon>>,
+    mut markers: Query<(Entity, &MinimapMarker, &mut Style)>,
+) {
+    let panel = match panels.get_single() {
+        Ok(panel) => panel,
+        Err(_) => return,
+    };
+
+    for (target, icon) in new_icons.iter() {
+        let marker = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Px(icon.size), Val::Px(icon.size)),
+                    ..default()
+                },
+                color: icon.color.into(),
+                ..default()
+            })
+            .insert(MinimapMarker {
+                target,
+                size: icon.size,
+            })
+            .id();
+        commands.entity(panel).add_child(marker);
+    }
+
+    for (entity, marker, mut style) in markers.iter_mut() {
+        match targets.get(marker.target) {
+            Ok(transform) => {
+                let position = to_minimap(transform.translation) - Vec2::splat(marker.size / 2.);
+                style.position = UiRect {
+                    left: Val::Px(position.x),
+                    top: Val::Px(position.y),
+                    ..default()
+                };
+            }
+            Err(_) => commands.entity(entity).despawn_recursive(),
+        }
+    }
+}
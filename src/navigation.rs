@@ -0,0 +1,565 @@
+use crate::game;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::f32::consts::FRAC_PI_4;
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use heron::prelude::*;
+
+pub const CELL_SIZE: f32 = 2.;
+// how far to keep the middle of a vehicle away from a fence
+const CLEARANCE: f32 = 2.5;
+// driving through standing crop squashes it, so prefer to go round
+const CROP_COST: f32 = 4.;
+// the autopilots plan every second on the main thread, so a search that's getting
+// nowhere gives up rather than holding up the frame
+const MAX_EXPANSIONS: usize = 50_000;
+// over-weighting the distance left settles for a route a little longer than the best
+// one, in exchange for looking at far fewer states on the way
+const HEURISTIC_WEIGHT: f32 = 2.;
+// how far ahead an animal checks for a fence before it sets off
+const ANIMAL_LOOKAHEAD: f32 = 3.;
+
+const HEADINGS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+pub struct NavGrid {
+    origin: Vec2,
+    width: usize,
+    height: usize,
+    // f32::INFINITY for cells that can't be driven through
+    cost: Vec<f32>,
+    // kept between searches, so each one doesn't have to allocate and clear its own
+    search: Mutex<SearchSpace>,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        NavGrid {
+            origin: Vec2::ZERO,
+            width: 0,
+            height: 0,
+            cost: Vec::new(),
+            search: Mutex::new(SearchSpace::default()),
+        }
+    }
+}
+
+// per-state bookkeeping for find_path, indexed by SearchState::index
+#[derive(Default)]
+struct SearchSpace {
+    // bumped every search, so anything stamped with an older one counts as unseen
+    generation: u32,
+    seen_in: Vec<u32>,
+    best_cost: Vec<f32>,
+    came_from: Vec<usize>,
+    // which patch of open ground each cell is in, 0 for fenced off; filled in by the
+    // first search, and thrown away with the grid when a fence goes up
+    regions: Vec<u32>,
+    // (start, goal) cells a search has already run out of budget on, so a vehicle
+    // that's parked doesn't try the same one every replan
+    gave_up_on: HashSet<((i32, i32), (i32, i32))>,
+}
+
+impl SearchSpace {
+    fn start(&mut self, states: usize) {
+        if self.seen_in.len() < states {
+            self.seen_in.resize(states, 0);
+            self.best_cost.resize(states, 0.);
+            self.came_from.resize(states, 0);
+        }
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            // wrapped round, so old stamps could be mistaken for this search's
+            self.seen_in.fill(0);
+            self.generation = 1;
+        }
+    }
+
+    fn cost_of(&self, index: usize) -> Option<f32> {
+        (self.seen_in[index] == self.generation).then_some(self.best_cost[index])
+    }
+
+    fn reach(&mut self, index: usize, cost: f32, from: usize) {
+        self.seen_in[index] = self.generation;
+        self.best_cost[index] = cost;
+        self.came_from[index] = from;
+    }
+}
+
+#[derive(Component, Default)]
+pub struct NavRoute {
+    pub waypoints: Vec<Vec3>,
+}
+
+#[derive(Component)]
+pub struct RouteMarker {
+    owner: Entity,
+}
+
+#[derive(Default)]
+pub struct RoutePreview {
+    pub enabled: bool,
+    marker_mesh: Option<Handle<Mesh>>,
+    marker_material: Option<Handle<StandardMaterial>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct SearchState {
+    x: i32,
+    z: i32,
+    heading: usize,
+    // cells driven since the last turn, capped at the number needed before turning again
+    straight: usize,
+}
+
+impl SearchState {
+    fn index(&self, width: usize, cells_per_turn: usize) -> usize {
+        let cell = self.z as usize * width + self.x as usize;
+        (cell * HEADINGS.len() + self.heading) * (cells_per_turn + 1) + self.straight
+    }
+
+    fn from_index(index: usize, width: usize, cells_per_turn: usize) -> SearchState {
+        let straight = index % (cells_per_turn + 1);
+        let index = index / (cells_per_turn + 1);
+        let heading = index % HEADINGS.len();
+        let cell = index / HEADINGS.len();
+        SearchState {
+            x: (cell % width) as i32,
+            z: (cell / width) as i32,
+            heading,
+            straight,
+        }
+    }
+}
+
+struct OpenEntry {
+    estimate: f32,
+    state: SearchState,
+}
+
+impl PartialEq for OpenEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for OpenEntry {}
+
+impl PartialOrd for OpenEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenEntry {
+    // BinaryHeap is a max-heap, we want the cheapest first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl NavGrid {
+    pub fn new(half_size: f32) -> Self {
+        let cells = (2. * half_size / CELL_SIZE).ceil() as usize;
+        NavGrid {
+            origin: Vec2::new(-half_size, -half_size),
+            width: cells,
+            height: cells,
+            cost: vec![1.; cells * cells],
+            search: Mutex::new(SearchSpace::default()),
+        }
+    }
+
+    fn cell_of(&self, point: Vec3) -> Option<(i32, i32)> {
+        let x = ((point.x - self.origin.x) / CELL_SIZE).floor() as i32;
+        let z = ((point.z - self.origin.y) / CELL_SIZE).floor() as i32;
+        if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.height {
+            return None;
+        }
+        Some((x, z))
+    }
+
+    fn cell_centre(&self, x: i32, z: i32) -> Vec3 {
+        Vec3::new(
+            self.origin.x + (x as f32 + 0.5) * CELL_SIZE,
+            0.,
+            self.origin.y + (z as f32 + 0.5) * CELL_SIZE,
+        )
+    }
+
+    // flood fill, so find_path can turn down a goal on the far side of a fence straight away
+    fn label_regions(&self) -> Vec<u32> {
+        let mut regions = vec![0; self.cost.len()];
+        let mut next_region = 0;
+        let mut frontier = VecDeque::new();
+        for first in 0..self.cost.len() {
+            if regions[first] != 0 || self.cost[first].is_infinite() {
+                continue;
+            }
+            next_region += 1;
+            regions[first] = next_region;
+            frontier.push_back(first);
+            while let Some(index) = frontier.pop_front() {
+                let (x, z) = ((index % self.width) as i32, (index / self.width) as i32);
+                for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    let (x, z) = (x + dx, z + dz);
+                    if self.cell_cost(x, z).is_infinite() {
+                        continue;
+                    }
+                    let neighbour = z as usize * self.width + x as usize;
+                    if regions[neighbour] == 0 {
+                        regions[neighbour] = next_region;
+                        frontier.push_back(neighbour);
+                    }
+                }
+            }
+        }
+        regions
+    }
+
+    fn cell_cost(&self, x: i32, z: i32) -> f32 {
+        if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.height {
+            return f32::INFINITY;
+        }
+        self.cost[z as usize * self.width + x as usize]
+    }
+
+    // whether anything on foot can stand at `point`, fences and all
+    pub fn is_open(&self, point: Vec3) -> bool {
+        self.cell_of(point)
+            .is_some_and(|(x, z)| self.cell_cost(x, z).is_finite())
+    }
+
+    // `direction`, or the nearest one to it that doesn't walk straight into a fence
+    pub fn clear_direction(&self, position: Vec3, direction: Vec3) -> Vec3 {
+        // already up against one, so any way out will do
+        if !self.is_open(position) {
+            return direction;
+        }
+        for turns in [0., 1., -1., 2., -2., 3., -3., 4.] {
+            let turned = Quat::from_rotation_y(turns * FRAC_PI_4) * direction;
+            if self.is_open(position + turned * ANIMAL_LOOKAHEAD) {
+                return turned;
+            }
+        }
+        direction
+    }
+
+    fn set_cost_in(&mut self, min: Vec2, max: Vec2, cost: f32) {
+        let min_x = (((min.x - self.origin.x) / CELL_SIZE).floor() as i32).max(0);
+        let min_z = (((min.y - self.origin.y) / CELL_SIZE).floor() as i32).max(0);
+        let max_x = (((max.x - self.origin.x) / CELL_SIZE).ceil() as i32).min(self.width as i32);
+        let max_z = (((max.y - self.origin.y) / CELL_SIZE).ceil() as i32).min(self.height as i32);
+        for z in min_z..max_z {
+            for x in min_x..max_x {
+                let index = z as usize * self.width + x as usize;
+                if self.cost[index].is_finite() {
+                    self.cost[index] = cost;
+                }
+            }
+        }
+    }
+
+    pub fn add_field(&mut self, field: &game::Field) {
+        self.set_cost_in(
+            field.position - field.half_size,
+            field.position + field.half_size,
+            CROP_COST,
+        );
+    }
+
+    pub fn add_obstacle(&mut self, transform: &Transform, half_extends: Vec3) {
+        let corner = transform.rotation * half_extends;
+        let other_corner = transform.rotation * Vec3::new(-half_extends.x, 0., half_extends.z);
+        let reach = Vec2::new(
+            corner.x.abs().max(other_corner.x.abs()) + CLEARANCE,
+            corner.z.abs().max(other_corner.z.abs()) + CLEARANCE,
+        );
+        let centre = Vec2::new(transform.translation.x, transform.translation.z);
+        self.set_cost_in(centre - reach, centre + reach, f32::INFINITY);
+    }
+
+    // A* over (cell, heading), only allowing a 45 degree turn once the vehicle has covered
+    // the arc length that turn takes at its turning radius
+    pub fn find_path(
+        &self,
+        from: Vec3,
+        heading: Vec3,
+        to: Vec3,
+        turning_radius: f32,
+    ) -> Option<Vec<Vec3>> {
+        let (start_x, start_z) = self.cell_of(from)?;
+        let (goal_x, goal_z) = self.cell_of(to)?;
+        if self.cell_cost(goal_x, goal_z).is_infinite() {
+            return None;
+        }
+        let mut search = self.search.lock().unwrap();
+        if search.regions.is_empty() {
+            search.regions = self.label_regions();
+        }
+        let region = |x: i32, z: i32| search.regions[z as usize * self.width + x as usize];
+        // a vehicle brushing a fence stands in a closed cell, but can still drive out of it
+        let start_region = region(start_x, start_z);
+        if (start_region != 0 && start_region != region(goal_x, goal_z))
+            || search
+                .gave_up_on
+                .contains(&((start_x, start_z), (goal_x, goal_z)))
+        {
+            return None;
+        }
+
+        let cells_per_turn = (turning_radius * FRAC_PI_4 / CELL_SIZE).ceil().max(0.) as usize;
+        let start_heading = ((heading.z.atan2(heading.x) / FRAC_PI_4).round() as i32)
+            .rem_euclid(HEADINGS.len() as i32) as usize;
+        let start = SearchState {
+            x: start_x,
+            z: start_z,
+            heading: start_heading,
+            straight: cells_per_turn,
+        };
+        let estimate = |x: i32, z: i32| {
+            Vec2::new((goal_x - x) as f32, (goal_z - z) as f32).length() * HEURISTIC_WEIGHT
+        };
+
+        let index = |state: &SearchState| state.index(self.width, cells_per_turn);
+
+        search.start(self.width * self.height * HEADINGS.len() * (cells_per_turn + 1));
+        let mut open = BinaryHeap::new();
+        search.reach(index(&start), 0., index(&start));
+        open.push(OpenEntry {
+            estimate: estimate(start_x, start_z),
+            state: start,
+        });
+
+        let mut expansions = 0;
+        while let Some(OpenEntry { state, .. }) = open.pop() {
+            if state.x == goal_x && state.z == goal_z {
+                return Some(self.rebuild_path(&search, index(&state), cells_per_turn, to));
+            }
+            expansions += 1;
+            if expansions > MAX_EXPANSIONS {
+                break;
+            }
+
+            let cost_so_far = search.best_cost[index(&state)];
+            for turn in [-1, 0, 1] {
+                if turn != 0 && state.straight < cells_per_turn {
+                    continue;
+                }
+                let heading =
+                    (state.heading as i32 + turn).rem_euclid(HEADINGS.len() as i32) as usize;
+                let (dx, dz) = HEADINGS[heading];
+                let (x, z) = (state.x + dx, state.z + dz);
+                let mut step_cost = self.cell_cost(x, z);
+                if dx != 0 && dz != 0 {
+                    // no cutting corners past fence posts
+                    step_cost = step_cost
+                        .max(self.cell_cost(state.x + dx, state.z))
+                        .max(self.cell_cost(state.x, state.z + dz));
+                    step_cost *= std::f32::consts::SQRT_2;
+                }
+                if step_cost.is_infinite() {
+                    continue;
+                }
+
+                let next = SearchState {
+                    x,
+                    z,
+                    heading,
+                    straight: if turn == 0 {
+                        (state.straight + 1).min(cells_per_turn)
+                    } else {
+                        0
+                    },
+                };
+                let next_cost = cost_so_far + step_cost;
+                let next_index = index(&next);
+                if search
+                    .cost_of(next_index)
+                    .is_none_or(|cost| next_cost < cost)
+                {
+                    search.reach(next_index, next_cost, index(&state));
+                    open.push(OpenEntry {
+                        estimate: next_cost + estimate(x, z),
+                        state: next,
+                    });
+                }
+            }
+        }
+        // the callers fall back on heading straight for the goal, and try again from
+        // wherever that gets them
+        search
+            .gave_up_on
+            .insert(((start_x, start_z), (goal_x, goal_z)));
+        None
+    }
+
+    // only keep the cells where the heading changes
+    fn rebuild_path(
+        &self,
+        search: &SearchSpace,
+        goal: usize,
+        cells_per_turn: usize,
+        to: Vec3,
+    ) -> Vec<Vec3> {
+        let mut waypoints = vec![to];
+        let mut current = goal;
+        // the start state is the only one that came from itself
+        while search.came_from[current] != current {
+            let previous = search.came_from[current];
+            let (from, to) = (
+                SearchState::from_index(previous, self.width, cells_per_turn),
+                SearchState::from_index(current, self.width, cells_per_turn),
+            );
+            if from.heading != to.heading {
+                waypoints.push(self.cell_centre(from.x, from.z));
+            }
+            current = previous;
+        }
+        waypoints.reverse();
+        waypoints
+    }
+}
+
+// drops any waypoints already reached and returns the one to head for next, only touching
+// the route mutably when it actually changes so the preview isn't rebuilt every frame
+pub fn advance_route(route: &mut Mut<NavRoute>, position: Vec3, radius: f32) -> Option<Vec3> {
+    let reached = route
+        .waypoints
+        .iter()
+        .take_while(|waypoint| {
+            Vec2::new(waypoint.x - position.x, waypoint.z - position.z).length() < radius
+        })
+        .count();
+    if reached > 0 {
+        route.waypoints.drain(..reached);
+    }
+    route.waypoints.first().copied()
+}
+
+// rebuilt whenever fences are spawned, so anything fenced in at setup gets included
+pub fn build_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    game: Res<game::Game>,
+    new_fences: Query<(), Added<game::Fence>>,
+    fences: Query<(&Transform, &CollisionShape), With<game::Fence>>,
+) {
+    if new_fences.is_empty() {
+        return;
+    }
+
+    let mut grid = NavGrid::new(game::GROUND_HALF_SIZE as f32);
+    for field in game.fields.iter() {
+        grid.add_field(field);
+    }
+    for (transform, shape) in fences.iter() {
+        if let CollisionShape::Cuboid { half_extends, .. } = shape {
+            grid.add_obstacle(transform, *half_extends);
+        }
+    }
+    *nav_grid = grid;
+}
+
+pub fn toggle_route_preview(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut preview: ResMut<RoutePreview>,
+    mut routes: Query<&mut NavRoute>,
+) {
+    if keyboard_input.just_pressed(KeyCode::P) {
+        preview.enabled = !preview.enabled;
+        // touch every route so the markers get rebuilt (or cleared)
+        for mut route in routes.iter_mut() {
+            route.set_changed();
+        }
+    }
+}
+
+pub fn update_route_preview(
+    mut commands: Commands,
+    mut preview: ResMut<RoutePreview>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    routes: Query<(Entity, &NavRoute), Changed<NavRoute>>,
+    markers: Query<(Entity, &RouteMarker)>,
+) {
+    for (owner, route) in routes.iter() {
+        for (marker, marker_owner) in markers.iter() {
+            if marker_owner.owner == owner {
+                commands.entity(marker).despawn();
+            }
+        }
+        if !preview.enabled {
+            continue;
+        }
+
+        if preview.marker_mesh.is_none() {
+            preview.marker_mesh = Some(meshes.add(Mesh::from(shape::UVSphere {
+                radius: 0.5,
+                ..default()
+            })));
+            preview.marker_material = Some(materials.add(StandardMaterial {
+                base_color: Color::rgba(1.0, 0.9, 0.2, 0.8),
+                unlit: true,
+                ..default()
+            }));
+        }
+        for waypoint in route.waypoints.iter() {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: preview.marker_mesh.clone().unwrap(),
+                    material: preview.marker_material.clone().unwrap(),
+                    transform: Transform::from_translation(*waypoint + Vec3::Y),
+                    ..default()
+                })
+                .insert(RouteMarker { owner });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall_grid() -> NavGrid {
+        let mut grid = NavGrid::new(20.);
+        // a wall across the middle, with a gap at the far end
+        grid.add_obstacle(&Transform::from_xyz(-4., 0., 0.), Vec3::new(12., 1., 0.5));
+        grid
+    }
+
+    #[test]
+    fn goes_round_a_wall() {
+        let grid = wall_grid();
+        let from = Vec3::new(-4., 0., -10.);
+        let to = Vec3::new(-4., 0., 10.);
+        let path = grid.find_path(from, Vec3::Z, to, 2.).unwrap();
+        assert_eq!(*path.last().unwrap(), to);
+        assert!(path.iter().any(|waypoint| waypoint.x > 8.), "{:?}", path);
+        assert!(path.iter().all(|waypoint| grid.is_open(*waypoint)));
+    }
+
+    #[test]
+    fn gives_up_on_a_goal_it_cannot_reach() {
+        let mut grid = wall_grid();
+        // close the gap
+        grid.add_obstacle(&Transform::from_xyz(14., 0., 0.), Vec3::new(6., 1., 0.5));
+        let from = Vec3::new(-4., 0., -10.);
+        let to = Vec3::new(-4., 0., 10.);
+        assert!(grid.find_path(from, Vec3::Z, to, 2.).is_none());
+        // the fence splits the grid in two, so it doesn't search at all
+        assert_eq!(grid.search.lock().unwrap().generation, 0);
+    }
+}
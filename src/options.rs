@@ -0,0 +1,340 @@
+use crate::game;
+use crate::game::{GameState, Level};
+use crate::herding::HerdingObjective;
+use crate::layout::MapLayout;
+
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy::window::{WindowMode, WindowSettings};
+use bevy::winit::WinitPlugin;
+use std::time::Duration;
+
+const USAGE: &str = "options:
+  --level <file>    play on the fields laid out in <file> (see assets/farm.level)
+  --seed <number>   start every round from this seed
+  --timer <seconds> how long a round lasts
+  --start <where>   menu, shop, dealership, harvest, career or tutorial
+  --herding         start with the sheepdog objective switched on
+  --windowed        run in a window
+  --fullscreen      run fullscreen
+  --headless        play the round with no window, as fast as it'll go, then print the
+                    score and quit
+In the browser, the same options go in the page's query string,
+e.g. ?seed=42&timer=60&start=harvest&herding";
+
+// the options that take a value; the rest are plain switches
+const VALUE_OPTIONS: [&str; 4] = ["level", "seed", "timer", "start"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StartingPoint {
+    Menu,
+    Shop,
+    Dealership,
+    // straight into a round, skipping the menu and dealership
+    Round(Level),
+}
+
+impl StartingPoint {
+    fn from_name(name: &str) -> Option<StartingPoint> {
+        match name {
+            "menu" => Some(StartingPoint::Menu),
+            "shop" => Some(StartingPoint::Shop),
+            "dealership" => Some(StartingPoint::Dealership),
+            "harvest" => Some(StartingPoint::Round(Level::Harvest)),
+            "career" => Some(StartingPoint::Round(Level::Career)),
+            "tutorial" => Some(StartingPoint::Round(Level::Tutorial)),
+            _ => None,
+        }
+    }
+}
+
+// what was asked for on the command line, or in the URL for the web build
+pub struct Options {
+    pub layout: MapLayout,
+    pub seed: Option<u64>,
+    // replaces each level's own round length
+    pub timer: Option<Duration>,
+    pub start: StartingPoint,
+    // the menu can still switch it either way
+    pub herding: bool,
+    pub window_mode: WindowMode,
+    pub headless: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            layout: MapLayout::default(),
+            seed: None,
+            timer: None,
+            start: StartingPoint::Menu,
+            herding: false,
+            window_mode: WindowMode::Windowed,
+            headless: false,
+        }
+    }
+}
+
+impl Options {
+    // anything wrong stops the game before it starts, rather than being quietly ignored
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_environment() -> Options {
+        match split_args(std::env::args().skip(1)).and_then(Options::from_pairs) {
+            Ok(options) => options,
+            Err(error) => {
+                eprintln!("{}\n\n{}", error, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // a page can't be exited, so say what was wrong and carry on with the defaults
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_environment() -> Options {
+        let window = web_sys::window();
+        let query = window
+            .as_ref()
+            .and_then(|window| window.location().search().ok())
+            .unwrap_or_default();
+        match Options::from_pairs(split_query(&query)) {
+            Ok(options) => options,
+            Err(error) => {
+                if let Some(window) = window {
+                    let _ = window.alert_with_message(&format!("{}\n\n{}", error, USAGE));
+                }
+                Options::default()
+            }
+        }
+    }
+
+    fn from_pairs(pairs: Vec<(String, Option<String>)>) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut windowed = false;
+        let mut fullscreen = false;
+        for (name, value) in pairs {
+            let takes_value = VALUE_OPTIONS.contains(&name.as_str());
+            let value = match (takes_value, value) {
+                (true, Some(value)) => value,
+                (true, None) => return Err(format!("--{} needs a value", name)),
+                (false, Some(_)) => return Err(format!("--{} doesn't take a value", name)),
+                (false, None) => String::new(),
+            };
+            match name.as_str() {
+                "level" => options.layout = MapLayout::from_file(&value)?,
+                "seed" => {
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("--seed needs a whole number, not '{}'", value))?;
+                    options.seed = Some(seed);
+                }
+                "timer" => {
+                    let seconds = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|seconds| *seconds > 0. && *seconds <= game::MAX_ROUND_SECONDS);
+                    let seconds = seconds.ok_or_else(|| {
+                        format!(
+                            "--timer needs a number of seconds above zero and at most {}, \
+                             not '{}'",
+                            game::MAX_ROUND_SECONDS,
+                            value
+                        )
+                    })?;
+                    options.timer = Some(Duration::from_secs_f32(seconds));
+                }
+                "start" => {
+                    options.start = StartingPoint::from_name(&value).ok_or_else(|| {
+                        format!(
+                            "--start needs one of menu, shop, dealership, harvest, career \
+                             or tutorial, not '{}'",
+                            value
+                        )
+                    })?;
+                }
+                "herding" => options.herding = true,
+                "windowed" => windowed = true,
+                "fullscreen" => fullscreen = true,
+                "headless" => options.headless = true,
+                _ => return Err(format!("unknown option --{}", name)),
+            }
+        }
+
+        if windowed && fullscreen {
+            return Err("--windowed and --fullscreen can't both be given".to_string());
+        }
+        if fullscreen {
+            options.window_mode = WindowMode::BorderlessFullscreen;
+        }
+        if options.headless {
+            if windowed || fullscreen {
+                return Err("--headless has no window to be windowed or fullscreen".to_string());
+            }
+            if !matches!(options.start, StartingPoint::Round(_)) {
+                return Err(
+                    "--headless needs a round to play, such as --start harvest, \
+                     as there's nobody to press the buttons"
+                        .to_string(),
+                );
+            }
+            if cfg!(target_arch = "wasm32") {
+                return Err("--headless isn't available in the browser".to_string());
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn initial_state(&self) -> GameState {
+        match self.start {
+            StartingPoint::Menu => GameState::Menu,
+            StartingPoint::Shop => GameState::Shop,
+            StartingPoint::Dealership => GameState::Dealership,
+            StartingPoint::Round(_) => GameState::Playing,
+        }
+    }
+
+    // in place of DefaultPlugins on its own, so the window can be set up (or left out)
+    pub fn add_default_plugins(&self, app: &mut App) {
+        if self.headless {
+            // nothing gets drawn, and the schedule runner drives the frames instead of winit.
+            // It doesn't wait between them, so replay::start_round puts the round on the
+            // fixed clock, and the round runs faster than real time
+            app.insert_resource(WgpuSettings {
+                backends: None,
+                ..default()
+            })
+            .insert_resource(WindowSettings {
+                add_primary_window: false,
+                exit_on_all_closed: false,
+                ..default()
+            })
+            .add_plugins_with(DefaultPlugins, |group| group.disable::<WinitPlugin>())
+            .add_plugin(ScheduleRunnerPlugin);
+        } else {
+            app.insert_resource(WindowDescriptor {
+                mode: self.window_mode,
+                ..default()
+            })
+            .add_plugins(DefaultPlugins);
+        }
+    }
+}
+
+// `--name value` and `--name=value` both work
+#[cfg(not(target_arch = "wasm32"))]
+fn split_args(args: impl Iterator<Item = String>) -> Result<Vec<(String, Option<String>)>, String> {
+    let mut args = args.peekable();
+    let mut pairs = Vec::new();
+    while let Some(arg) = args.next() {
+        let option = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("expected an option starting with --, not '{}'", arg))?;
+        match option.split_once('=') {
+            Some((name, value)) => pairs.push((name.to_string(), Some(value.to_string()))),
+            None => {
+                let value = if VALUE_OPTIONS.contains(&option) {
+                    args.next_if(|next| !next.starts_with("--"))
+                } else {
+                    None
+                };
+                pairs.push((option.to_string(), value));
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+// `?seed=42&headless` gives the same as `--seed=42 --headless`
+#[cfg(target_arch = "wasm32")]
+fn split_query(query: &str) -> Vec<(String, Option<String>)> {
+    query
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (pair.to_string(), None),
+        })
+        .collect()
+}
+
+// the level to play when the game starts straight into a round, and whether it herds
+pub fn apply_start(
+    options: Res<Options>,
+    mut game: ResMut<game::Game>,
+    mut objective: ResMut<HerdingObjective>,
+) {
+    if let StartingPoint::Round(level) = options.start {
+        game.level = level;
+    }
+    objective.enabled = options.herding;
+}
+
+// with no one watching, the score is all there is to show for the round
+pub fn finish_headless(
+    options: Res<Options>,
+    game: Res<game::Game>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if options.headless {
+        println!("final score: {}", game.score);
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        split_args(args.split_whitespace().map(str::to_string)).and_then(Options::from_pairs)
+    }
+
+    #[test]
+    fn values_and_switches() {
+        let options = parse("--seed 42 --timer=30 --start harvest --herding --headless").unwrap();
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.timer, Some(Duration::from_secs(30)));
+        assert_eq!(options.start, StartingPoint::Round(Level::Harvest));
+        assert!(options.herding);
+        assert!(options.headless);
+    }
+
+    #[test]
+    fn seed_must_be_a_number() {
+        let error = parse("--seed x").err().unwrap();
+        assert!(error.contains("--seed"), "{}", error);
+    }
+
+    #[test]
+    fn seed_needs_a_value() {
+        let error = parse("--start harvest --seed").err().unwrap();
+        assert_eq!(error, "--seed needs a value");
+    }
+
+    #[test]
+    fn timer_must_be_above_zero() {
+        let error = parse("--timer 0").err().unwrap();
+        assert!(error.contains("--timer"), "{}", error);
+    }
+
+    #[test]
+    fn timer_must_fit_a_round() {
+        for timer in ["inf", "NaN", "1e30", "86401"] {
+            let error = parse(&format!("--timer {}", timer)).err().unwrap();
+            assert!(error.contains("--timer"), "{}", error);
+        }
+        assert!(parse("--timer 86400").is_ok());
+    }
+
+    #[test]
+    fn windowed_and_fullscreen_clash() {
+        assert!(parse("--windowed --fullscreen").is_err());
+    }
+
+    #[test]
+    fn headless_needs_a_round() {
+        assert!(parse("--headless").is_err());
+        assert!(parse("--headless --start shop").is_err());
+    }
+}
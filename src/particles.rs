@@ -0,0 +1,333 @@
+use crate::drop_zone::GrainDeliveredEvent;
+use crate::harvest::CropHarvestedEvent;
+use crate::vehicles::GrainTransferEvent;
+use crate::weather::Weather;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use heron::prelude::*;
+use rand::Rng;
+
+const MAX_PARTICLES: usize = 2000;
+const GRAVITY: f32 = 9.81;
+
+// how long each plant cut keeps the chaff coming out the back
+const CHAFF_PER_PLANT: f32 = 0.15;
+// grain units the auger moves per second, which decides how long the stream runs
+const AUGER_RATE: f32 = 250.;
+const POUR_RATE: f32 = 400.;
+const MAX_STREAM_TIME: f32 = 3.;
+
+// dust per second at full speed, and the speed below which there isn't any
+const DUST_RATE: f32 = 40.;
+const DUST_MIN_SPEED: f32 = 2.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParticleKind {
+    Chaff,
+    Grain,
+    Dust,
+}
+
+impl ParticleKind {
+    fn color(&self) -> Color {
+        match self {
+            ParticleKind::Chaff => Color::rgba(0.9, 0.8, 0.5, 0.8),
+            ParticleKind::Grain => Color::rgba(0.85, 0.65, 0.2, 1.),
+            ParticleKind::Dust => Color::rgba(0.6, 0.5, 0.4, 0.4),
+        }
+    }
+
+    fn size(&self) -> f32 {
+        match self {
+            ParticleKind::Chaff => 0.4,
+            ParticleKind::Grain => 0.2,
+            ParticleKind::Dust => 1.2,
+        }
+    }
+
+    fn lifetime(&self) -> f32 {
+        match self {
+            ParticleKind::Chaff => 1.5,
+            ParticleKind::Grain => 1.,
+            ParticleKind::Dust => 2.,
+        }
+    }
+
+    // how much gravity pulls on it; dust just drifts
+    fn weight(&self) -> f32 {
+        match self {
+            ParticleKind::Chaff => 0.2,
+            ParticleKind::Grain => 1.,
+            ParticleKind::Dust => -0.05,
+        }
+    }
+
+    // dust puffs up as it spreads, everything else shrinks away
+    fn end_scale(&self) -> f32 {
+        match self {
+            ParticleKind::Dust => 3.,
+            _ => 0.3,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    kind: ParticleKind,
+    velocity: Vec3,
+    age: f32,
+}
+
+// sprays particles from wherever it is, as long as it's active
+#[derive(Component)]
+pub struct Emitter {
+    pub kind: ParticleKind,
+    // per second
+    pub rate: f32,
+    // initial velocity, in the emitter's own frame
+    pub velocity: Vec3,
+    pub spread: f32,
+    // seconds left before it stops
+    pub active_for: f32,
+    // for one-off effects that aren't attached to anything
+    pub despawn_when_done: bool,
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(kind: ParticleKind, rate: f32, velocity: Vec3, spread: f32) -> Self {
+        Emitter {
+            kind,
+            rate,
+            velocity,
+            spread,
+            active_for: 0.,
+            despawn_when_done: false,
+            accumulator: 0.,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ChaffChute {}
+
+#[derive(Component)]
+pub struct Auger {}
+
+// kicks up dust behind the rear wheels when moving
+#[derive(Component)]
+pub struct DustTrail {
+    pub half_width: f32,
+    pub rear: f32,
+    accumulator: f32,
+}
+
+impl DustTrail {
+    pub fn new(half_width: f32, rear: f32) -> Self {
+        DustTrail {
+            half_width,
+            rear,
+            accumulator: 0.,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ParticleAssets {
+    mesh: Option<Handle<Mesh>>,
+    // indexed by ParticleKind
+    materials: [Option<Handle<StandardMaterial>>; 3],
+}
+
+// everything it takes to put a particle in the world, up to MAX_PARTICLES of them
+#[derive(SystemParam)]
+pub struct ParticleSpawner<'w, 's> {
+    commands: Commands<'w, 's>,
+    assets: ResMut<'w, ParticleAssets>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    particles: Query<'w, 's, (), With<Particle>>,
+    // how many more this frame; set by start_frame
+    #[system_param(ignore)]
+    budget: usize,
+}
+
+impl<'w, 's> ParticleSpawner<'w, 's> {
+    fn start_frame(&mut self) {
+        self.budget = MAX_PARTICLES.saturating_sub(self.particles.iter().count());
+    }
+
+    fn spawn(&mut self, kind: ParticleKind, position: Vec3, velocity: Vec3) {
+        if self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+
+        let meshes = &mut self.meshes;
+        let mesh = self
+            .assets
+            .mesh
+            .get_or_insert_with(|| meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE))))
+            .clone();
+        let materials = &mut self.materials;
+        let material = self.assets.materials[kind as usize]
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: kind.color(),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .clone();
+        self.commands
+            .spawn_bundle(PbrBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(position)
+                    .with_scale(Vec3::splat(kind.size())),
+                ..default()
+            })
+            .insert(Particle {
+                kind,
+                velocity,
+                age: 0.,
+            });
+    }
+}
+
+fn jitter(rng: &mut impl Rng, spread: f32) -> Vec3 {
+    Vec3::new(
+        rng.gen_range(-spread..=spread),
+        rng.gen_range(-spread..=spread),
+        rng.gen_range(-spread..=spread),
+    )
+}
+
+// one-off pour of grain from `position`, e.g. into the drop zone
+pub fn spawn_pour(commands: &mut Commands, position: Vec3, duration: f32) {
+    let mut emitter = Emitter::new(ParticleKind::Grain, 120., Vec3::new(0., -2., 0.), 1.);
+    emitter.active_for = duration;
+    emitter.despawn_when_done = true;
+    commands
+        .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+            position,
+        )))
+        .insert(emitter);
+}
+
+pub fn trigger_emitters(
+    mut commands: Commands,
+    mut chutes: Query<&mut Emitter, (With<ChaffChute>, Without<Auger>)>,
+    mut augers: Query<&mut Emitter, (With<Auger>, Without<ChaffChute>)>,
+    mut crop_harvested_events: EventReader<CropHarvestedEvent>,
+    mut grain_transfer_events: EventReader<GrainTransferEvent>,
+    mut grain_delivered_events: EventReader<GrainDeliveredEvent>,
+) {
+    let plants_cut = crop_harvested_events.iter().count();
+    if plants_cut > 0 {
+        for mut chute in chutes.iter_mut() {
+            chute.active_for =
+                (chute.active_for + plants_cut as f32 * CHAFF_PER_PLANT).min(MAX_STREAM_TIME);
+        }
+    }
+    for event in grain_transfer_events.iter() {
+        for mut auger in augers.iter_mut() {
+            auger.active_for =
+                (auger.active_for + event.amount as f32 / AUGER_RATE).min(MAX_STREAM_TIME);
+        }
+    }
+    for event in grain_delivered_events.iter() {
+        spawn_pour(
+            &mut commands,
+            event.position,
+            (event.load.total() as f32 / POUR_RATE).clamp(0.3, MAX_STREAM_TIME),
+        );
+    }
+}
+
+pub fn update_emitters(
+    mut spawner: ParticleSpawner,
+    time: Res<Time>,
+    weather: Res<Weather>,
+    mut emitters: Query<(Entity, &mut Emitter, &GlobalTransform)>,
+    mut trails: Query<(&mut DustTrail, &Transform, &Velocity)>,
+) {
+    let delta = time.delta_seconds();
+    let mut rng = rand::thread_rng();
+    let mut despawned = Vec::new();
+    spawner.start_frame();
+
+    for (entity, mut emitter, global_transform) in emitters.iter_mut() {
+        if emitter.active_for <= 0. {
+            emitter.accumulator = 0.;
+            if emitter.despawn_when_done {
+                despawned.push(entity);
+            }
+            continue;
+        }
+        emitter.active_for -= delta;
+        emitter.accumulator += emitter.rate * delta;
+        let transform = global_transform.compute_transform();
+        while emitter.accumulator >= 1. {
+            emitter.accumulator -= 1.;
+            let velocity = transform.rotation * emitter.velocity + jitter(&mut rng, emitter.spread);
+            spawner.spawn(emitter.kind, transform.translation, velocity);
+        }
+    }
+
+    // wet ground doesn't throw up much dust
+    let dryness = 1. - weather.wetness;
+    for (mut trail, transform, velocity) in trails.iter_mut() {
+        let speed = Vec3::new(velocity.linear.x, 0., velocity.linear.z).length();
+        if speed < DUST_MIN_SPEED {
+            continue;
+        }
+        trail.accumulator += DUST_RATE * dryness * (speed / 10.).min(1.) * delta;
+        while trail.accumulator >= 1. {
+            trail.accumulator -= 1.;
+            let side = if rng.gen() { 1. } else { -1. };
+            let position = transform.translation
+                + transform.right() * side * trail.half_width
+                + transform.back() * trail.rear;
+            let drift = Vec3::new(0., 0.5, 0.) + jitter(&mut rng, 0.5);
+            spawner.spawn(
+                ParticleKind::Dust,
+                Vec3::new(position.x, 0.3, position.z),
+                drift,
+            );
+        }
+    }
+
+    for entity in despawned {
+        spawner.commands.entity(entity).despawn();
+    }
+}
+
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    cameras: Query<&Transform, (With<Camera3d>, Without<Particle>)>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+    // billboards, so they always face the camera
+    let facing = cameras
+        .get_single()
+        .map_or(Quat::IDENTITY, |camera| camera.rotation);
+    for (entity, mut particle, mut transform) in particles.iter_mut() {
+        particle.age += delta;
+        let lifetime = particle.kind.lifetime();
+        if particle.age >= lifetime || transform.translation.y < 0. {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        particle.velocity.y -= GRAVITY * particle.kind.weight() * delta;
+        transform.translation += particle.velocity * delta;
+        transform.rotation = facing;
+        let progress = particle.age / lifetime;
+        transform.scale =
+            Vec3::splat(particle.kind.size() * (1. + (particle.kind.end_scale() - 1.) * progress));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::game;
+use crate::game::{GameState, Level};
+use crate::options::Options;
+use crate::rng::GameRng;
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use bevy::window::PresentMode;
+use heron::PhysicsSteps;
+
+// seconds per frame while a round is being recorded or played back
+pub const FIXED_TIMESTEP: f64 = 1. / 60.;
+// how far the arrow keys skip, in frames
+const SEEK_STEP: usize = 600;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoundStart;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplayMode {
+    Off,
+    Recording,
+    Playing,
+}
+
+// Harvest rounds are recorded as the seed plus the keys held down on every frame.
+// With the clock and physics stepping a fixed amount each frame, feeding the same
+// keys back in plays the same round out again.
+pub struct Replay {
+    pub mode: ReplayMode,
+    seed: u64,
+    frames: Vec<Vec<KeyCode>>,
+    // the next frame to play back
+    frame: usize,
+    // fast-forwarding until playback reaches this frame
+    seek_to: Option<usize>,
+    // ticks FIXED_TIMESTEP a frame whatever the real frame rate, while it's there
+    clock: Option<Time>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay {
+            mode: ReplayMode::Off,
+            seed: 0,
+            frames: Vec::new(),
+            frame: 0,
+            seek_to: None,
+            clock: None,
+        }
+    }
+}
+
+impl Replay {
+    pub fn has_tape(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    // the round has been jumped about, so what's on tape would no longer play it back
+    pub fn abandon(&mut self) {
+        if self.mode == ReplayMode::Recording {
+            self.mode = ReplayMode::Off;
+            self.frames.clear();
+        }
+    }
+
+    fn seconds(frames: usize) -> u32 {
+        (frames as f64 * FIXED_TIMESTEP) as u32
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayViewer {}
+
+#[derive(Component)]
+pub struct ReplayText {}
+
+#[derive(Component)]
+pub struct ReplayProgress {}
+
+pub fn start_round(
+    mut replay: ResMut<Replay>,
+    mut rng: ResMut<GameRng>,
+    mut time: ResMut<Time>,
+    mut physics_steps: ResMut<PhysicsSteps>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    game: Res<game::Game>,
+    options: Res<Options>,
+) {
+    match replay.mode {
+        ReplayMode::Playing => {
+            replay.frame = 0;
+            rng.reseed(replay.seed);
+        }
+        _ if game.level == Level::Harvest => {
+            rng.new_round();
+            replay.mode = ReplayMode::Recording;
+            replay.seed = rng.seed();
+            replay.frames.clear();
+        }
+        _ => {
+            // a tape from an earlier round won't match whatever's played next
+            replay.mode = ReplayMode::Off;
+            replay.frames.clear();
+            rng.new_round();
+            // with no window, nothing waits on the display between frames, so wall
+            // time would give a different round on every machine
+            if !options.headless {
+                replay.clock = None;
+                *physics_steps = PhysicsSteps::default();
+                return;
+            }
+        }
+    }
+
+    // whatever was held down to start the round mustn't leak into its first frame
+    let held: Vec<KeyCode> = keyboard_input.get_pressed().copied().collect();
+    for key in held {
+        keyboard_input.reset(key);
+    }
+
+    let mut clock = Time::default();
+    clock.update_with_instant(clock.startup());
+    *time = clock.clone();
+    replay.clock = Some(clock);
+    *physics_steps = PhysicsSteps::every_frame(Duration::from_secs_f64(FIXED_TIMESTEP));
+}
+
+// bevy's own time system runs at the very start of the frame, so this overrides it
+pub fn tick_clock(mut replay: ResMut<Replay>, mut time: ResMut<Time>) {
+    if let Some(clock) = replay.clock.as_mut() {
+        let last = clock.last_update().unwrap_or_else(|| clock.startup());
+        clock.update_with_instant(last + Duration::from_secs_f64(FIXED_TIMESTEP));
+        *time = clock.clone();
+    }
+}
+
+// runs once the real keyboard has been read each frame
+pub fn record_or_play_input(
+    mut replay: ResMut<Replay>,
+    app_state: Res<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+) {
+    let in_round = *app_state.current() == GameState::Playing;
+    match replay.mode {
+        ReplayMode::Recording if in_round => {
+            let held = keyboard_input.get_pressed().copied().collect();
+            replay.frames.push(held);
+        }
+        ReplayMode::Playing => {
+            // the viewer reads its own keys as raw events, so the real ones can all go
+            keyboard_input.clear();
+            let held = if in_round {
+                replay.frames.get(replay.frame).cloned().unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            let released: Vec<KeyCode> = keyboard_input
+                .get_pressed()
+                .filter(|key| !held.contains(key))
+                .copied()
+                .collect();
+            for key in released {
+                keyboard_input.release(key);
+            }
+            for key in held {
+                keyboard_input.press(key);
+            }
+            if in_round {
+                replay.frame += 1;
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn setup_viewer(mut commands: Commands, replay: Res<Replay>, asset_server: Res<AssetServer>) {
+    if replay.mode != ReplayMode::Playing {
+        return;
+    }
+    let font_handle = asset_server.load("fonts/abel-regular.ttf");
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(30.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(40.), Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            color: Color::rgba(0., 0., 0., 0.45).into(),
+            ..default()
+        })
+        .insert(ReplayViewer {})
+        .with_children(|panel| {
+            panel
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_handle.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(ReplayText {});
+            panel
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Px(8.)),
+                        margin: UiRect {
+                            top: Val::Px(4.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    color: Color::rgba(1., 1., 1., 0.15).into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                            ..default()
+                        },
+                        color: Color::ORANGE.into(),
+                        ..default()
+                    })
+                    .insert(ReplayProgress {});
+                });
+            panel.spawn_bundle(TextBundle::from_section(
+                "Left/Right skip 10s, Home restarts, Esc stops",
+                TextStyle {
+                    font: font_handle,
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+// skipping back means playing the round again from the start, so it goes via Rewinding
+pub fn replay_viewer(
+    mut replay: ResMut<Replay>,
+    mut app_state: ResMut<State<GameState>>,
+    mut key_events: EventReader<KeyboardInput>,
+    mut windows: ResMut<Windows>,
+    mut texts: Query<&mut Text, With<ReplayText>>,
+    mut bars: Query<&mut Style, With<ReplayProgress>>,
+) {
+    if replay.mode != ReplayMode::Playing {
+        return;
+    }
+    let length = replay.frames.len();
+    let mut target = None;
+    let mut stop = false;
+    for event in key_events.iter() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        let from = replay.seek_to.unwrap_or(replay.frame);
+        match event.key_code {
+            Some(KeyCode::Left) => target = Some(from.saturating_sub(SEEK_STEP)),
+            Some(KeyCode::Right) => target = Some((from + SEEK_STEP).min(length)),
+            Some(KeyCode::Home) => target = Some(0),
+            Some(KeyCode::Escape) => stop = true,
+            _ => {}
+        }
+    }
+
+    // the timer may run out on the same frame, and already be heading there
+    if stop {
+        replay.mode = ReplayMode::Off;
+        let _ = app_state.set(GameState::GameOver);
+        return;
+    }
+    if let Some(target) = target {
+        replay.seek_to = Some(target);
+        // going back wins over the round ending on the same frame
+        if target < replay.frame {
+            let _ = app_state.overwrite_set(GameState::Rewinding);
+            return;
+        }
+    }
+
+    // run flat out without vsync until we get there
+    if replay.seek_to.is_some_and(|target| replay.frame >= target) {
+        replay.seek_to = None;
+    }
+    let present_mode = if replay.seek_to.is_some() {
+        PresentMode::Immediate
+    } else {
+        PresentMode::Fifo
+    };
+    if let Some(window) = windows.get_primary_mut() {
+        if window.present_mode() != present_mode {
+            window.set_present_mode(present_mode);
+        }
+    }
+
+    if let Ok(mut text) = texts.get_single_mut() {
+        let (now, total) = (Replay::seconds(replay.frame), Replay::seconds(length));
+        text.sections[0].value = format!(
+            "Replay {}:{:02} / {}:{:02}{}",
+            now / 60,
+            now % 60,
+            total / 60,
+            total % 60,
+            if replay.seek_to.is_some() {
+                " (skipping)"
+            } else {
+                ""
+            }
+        );
+    }
+    for mut style in bars.iter_mut() {
+        style.size.width = Val::Percent(100. * replay.frame as f32 / length.max(1) as f32);
+    }
+}
+
+pub fn end_round(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut physics_steps: ResMut<PhysicsSteps>,
+    mut windows: ResMut<Windows>,
+    viewers: Query<Entity, With<ReplayViewer>>,
+) {
+    replay.mode = ReplayMode::Off;
+    replay.clock = None;
+    replay.seek_to = None;
+    *physics_steps = PhysicsSteps::default();
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_present_mode(PresentMode::Fifo);
+    }
+    for viewer in viewers.iter() {
+        commands.entity(viewer).despawn_recursive();
+    }
+}
+
+pub fn watch_replay(
+    mut replay: ResMut<Replay>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    if replay.has_tape() && keyboard_input.just_pressed(KeyCode::R) {
+        keyboard_input.clear();
+        // not if Return went back to the menu on the same frame
+        if app_state.set(GameState::Playing).is_ok() {
+            replay.mode = ReplayMode::Playing;
+        }
+    }
+}
+
+// the old round was cleared away on the way in
+pub fn rewind(mut app_state: ResMut<State<GameState>>) {
+    let _ = app_state.set(GameState::Playing);
+}
@@ -0,0 +1,65 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// Each system that needs randomness draws from its own stream, so the order the
+// scheduler happens to run them in can't change what any of them get.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RngStream {
+    Fields,
+    Animals,
+    Weather,
+    Contracts,
+}
+
+const STREAM_COUNT: usize = 4;
+
+// all the randomness that affects play comes from here, so a round can be
+// played out again from its seed
+pub struct GameRng {
+    seed: u64,
+    // from --seed; every round starts from it rather than a fresh one
+    fixed_seed: Option<u64>,
+    streams: [StdRng; STREAM_COUNT],
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng {
+            seed,
+            fixed_seed: None,
+            streams: [0, 1, 2, 3].map(|index| stream_rng(seed, index)),
+        }
+    }
+
+    // with a fixed seed (from --seed) every round starts from it, rather than a fresh one
+    pub fn new(fixed_seed: Option<u64>) -> Self {
+        let mut rng = GameRng::from_seed(fixed_seed.unwrap_or_else(rand::random));
+        rng.fixed_seed = fixed_seed;
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // starts every stream over from `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        let fixed_seed = self.fixed_seed;
+        *self = GameRng::from_seed(seed);
+        self.fixed_seed = fixed_seed;
+    }
+
+    // the fixed seed if there is one, otherwise a fresh one
+    pub fn new_round(&mut self) {
+        self.reseed(self.fixed_seed.unwrap_or_else(rand::random));
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        &mut self.streams[stream as usize]
+    }
+}
+
+// seed_from_u64 scrambles its input, so neighbouring seeds still give unrelated streams
+fn stream_rng(seed: u64, index: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
@@ -0,0 +1,924 @@
+use crate::animals::{spawn_animal, Animal, AnimalKind};
+use crate::career::{decode_plants, encode_plants, Career};
+use crate::contracts::{Contract, Contracts};
+use crate::crop_grid::{CropCutter, CropGrid};
+use crate::dealership::VehicleChoice;
+use crate::game;
+use crate::game::Level;
+use crate::ghost::Ghosts;
+use crate::harvest::{CropLoad, CropType, CROP_TYPES};
+use crate::header::{set_raised, Header, HeaderChoice, HeaderKind};
+use crate::herding::{Dog, HerdingObjective};
+use crate::replay::{Replay, ReplayMode};
+use crate::ui::Notice;
+use crate::vehicles::{Combine, CombineStorage, Truck, TruckStorage};
+use crate::weather::{Weather, WeatherKind};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use heron::prelude::*;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_FILE: &str = "round.sav";
+// bump whenever the format changes, so old saves are turned away rather than misread
+const SAVE_VERSION: u32 = 4;
+
+pub const SAVE_KEY: KeyCode = KeyCode::F5;
+pub const LOAD_KEY: KeyCode = KeyCode::F9;
+
+#[derive(Clone, Copy)]
+struct BodyState {
+    transform: Transform,
+    linear: Vec3,
+    angular: Vec3,
+}
+
+impl BodyState {
+    fn new(transform: &Transform, velocity: &Velocity) -> Self {
+        BodyState {
+            transform: *transform,
+            linear: velocity.linear,
+            angular: velocity.angular.into(),
+        }
+    }
+
+    fn restore(&self, transform: &mut Transform, velocity: &mut Velocity) {
+        *transform = self.transform;
+        velocity.linear = self.linear;
+        velocity.angular = AxisAngle::from(self.angular);
+    }
+}
+
+// the forecast, and how soaked everything is; the light catches up by itself
+#[derive(Clone, Copy)]
+struct WeatherState {
+    current: WeatherKind,
+    next: WeatherKind,
+    time_to_change: f32,
+    wetness: f32,
+}
+
+impl WeatherState {
+    fn new(weather: &Weather) -> Self {
+        WeatherState {
+            current: weather.current,
+            next: weather.next,
+            time_to_change: weather.time_to_change,
+            wetness: weather.wetness,
+        }
+    }
+
+    fn restore(&self, weather: &mut Weather) {
+        weather.current = self.current;
+        weather.next = self.next;
+        weather.time_to_change = self.time_to_change;
+        weather.wetness = self.wetness;
+    }
+}
+
+// everything needed to pick a round back up where it was left
+pub struct SavedRound {
+    pub level: Level,
+    // the career day it was saved on; that day's takings are banked once it ends
+    day: u32,
+    score: i32,
+    // the round's full length, as a --timer or the console may have changed it
+    round_length: f32,
+    time_remaining: f32,
+    combine_model: Option<usize>,
+    truck_model: Option<usize>,
+    combine: BodyState,
+    truck: BodyState,
+    combine_load: CropLoad,
+    truck_load: CropLoad,
+    header: HeaderKind,
+    header_raised: bool,
+    // run-length encoded, as in the career save
+    fields: Vec<String>,
+    contracts: Contracts,
+    weather: WeatherState,
+    herding_completed: bool,
+    // only there when the round has the herding objective
+    dog: Option<BodyState>,
+    animals: Vec<(AnimalKind, Transform)>,
+}
+
+// a round loaded from the menu, waiting for the round it belongs in to be spawned
+#[derive(Default)]
+pub struct PendingRound {
+    pub round: Option<SavedRound>,
+}
+
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Harvest => "harvest",
+        Level::Tutorial => "tutorial",
+        Level::Career => "career",
+    }
+}
+
+fn parse_level(name: &str) -> Option<Level> {
+    [Level::Harvest, Level::Tutorial, Level::Career]
+        .into_iter()
+        .find(|level| level_name(*level) == name)
+}
+
+fn format_transform(transform: &Transform) -> String {
+    let (t, r) = (transform.translation, transform.rotation);
+    format!("{} {} {} {} {} {} {}", t.x, t.y, t.z, r.x, r.y, r.z, r.w)
+}
+
+fn format_body(body: &BodyState) -> String {
+    format!(
+        "{} {} {} {} {} {} {}",
+        format_transform(&body.transform),
+        body.linear.x,
+        body.linear.y,
+        body.linear.z,
+        body.angular.x,
+        body.angular.y,
+        body.angular.z
+    )
+}
+
+fn format_load(load: &CropLoad) -> String {
+    CROP_TYPES
+        .iter()
+        .map(|crop| load.amount(*crop).to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_model(model: Option<usize>) -> String {
+    model.map_or("-".to_string(), |index| index.to_string())
+}
+
+fn parse_floats<'a>(words: impl Iterator<Item = &'a str>, count: usize) -> Option<Vec<f32>> {
+    let values = words
+        .map(|word| word.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    (values.len() == count).then_some(values)
+}
+
+fn parse_transform(values: &[f32]) -> Transform {
+    Transform {
+        translation: Vec3::new(values[0], values[1], values[2]),
+        rotation: Quat::from_xyzw(values[3], values[4], values[5], values[6]).normalize(),
+        ..default()
+    }
+}
+
+fn parse_body<'a>(words: impl Iterator<Item = &'a str>) -> Option<BodyState> {
+    let values = parse_floats(words, 13)?;
+    Some(BodyState {
+        transform: parse_transform(&values[..7]),
+        linear: Vec3::new(values[7], values[8], values[9]),
+        angular: Vec3::new(values[10], values[11], values[12]),
+    })
+}
+
+fn parse_load<'a>(words: impl Iterator<Item = &'a str>) -> Option<CropLoad> {
+    let amounts = words
+        .map(|word| word.parse::<i32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if amounts.len() != CROP_TYPES.len() {
+        return None;
+    }
+    let mut load = CropLoad::default();
+    for (crop, amount) in CROP_TYPES.iter().zip(amounts) {
+        load.add(*crop, amount);
+    }
+    Some(load)
+}
+
+fn format_contract(contract: &Contract) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        contract.crop.info().name,
+        contract.amount,
+        contract.delivered,
+        contract.time_left,
+        contract.reward,
+        contract.penalty
+    )
+}
+
+fn parse_contract<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<Contract> {
+    let crop = words.next().and_then(CropType::from_name)?;
+    let amount = words.next()?.parse().ok()?;
+    let delivered = words.next()?.parse().ok()?;
+    let time_left = words.next()?.parse().ok()?;
+    let reward = words.next()?.parse().ok()?;
+    let penalty = words.next()?.parse().ok()?;
+    words.next().is_none().then_some(Contract {
+        crop,
+        amount,
+        delivered,
+        time_left,
+        reward,
+        penalty,
+    })
+}
+
+fn parse_contract_totals<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<Contracts> {
+    let completed = words.next()?.parse().ok()?;
+    let failed = words.next()?.parse().ok()?;
+    let time_to_offer = words.next()?.parse().ok()?;
+    words.next().is_none().then_some(Contracts {
+        active: Vec::new(),
+        completed,
+        failed,
+        time_to_offer,
+    })
+}
+
+fn parse_weather<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<WeatherState> {
+    let current = words.next().and_then(WeatherKind::from_name)?;
+    let next = words.next().and_then(WeatherKind::from_name)?;
+    let values = parse_floats(words, 2)?;
+    Some(WeatherState {
+        current,
+        next,
+        time_to_change: values[0],
+        wetness: values[1],
+    })
+}
+
+fn parse_animal<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<(AnimalKind, Transform)> {
+    let kind = words.next().and_then(AnimalKind::from_name)?;
+    let values = parse_floats(words, 7)?;
+    Some((kind, parse_transform(&values)))
+}
+
+fn parse_model(word: &str) -> Option<Option<usize>> {
+    match word {
+        "-" => Some(None),
+        index => index.parse().ok().map(Some),
+    }
+}
+
+impl SavedRound {
+    fn to_save_string(&self) -> String {
+        let mut lines = vec![
+            format!("version {}", SAVE_VERSION),
+            format!("level {}", level_name(self.level)),
+            format!("day {}", self.day),
+            format!("score {}", self.score),
+            format!("round_length {}", self.round_length),
+            format!("time_remaining {}", self.time_remaining),
+            format!(
+                "models {} {}",
+                format_model(self.combine_model),
+                format_model(self.truck_model)
+            ),
+            format!("combine {}", format_body(&self.combine)),
+            format!("truck {}", format_body(&self.truck)),
+            format!("combine_load {}", format_load(&self.combine_load)),
+            format!("truck_load {}", format_load(&self.truck_load)),
+            format!("header {} {}", self.header.name(), self.header_raised),
+        ];
+        for (index, plants) in self.fields.iter().enumerate() {
+            lines.push(format!("field {} {}", index, plants));
+        }
+        lines.push(format!(
+            "contracts {} {} {}",
+            self.contracts.completed, self.contracts.failed, self.contracts.time_to_offer
+        ));
+        for contract in self.contracts.active.iter() {
+            lines.push(format!("contract {}", format_contract(contract)));
+        }
+        lines.push(format!(
+            "weather {} {} {} {}",
+            self.weather.current.name(),
+            self.weather.next.name(),
+            self.weather.time_to_change,
+            self.weather.wetness
+        ));
+        lines.push(format!("herding_completed {}", self.herding_completed));
+        if let Some(dog) = &self.dog {
+            lines.push(format!("dog {}", format_body(dog)));
+        }
+        for (kind, transform) in self.animals.iter() {
+            lines.push(format!(
+                "animal {} {}",
+                kind.name(),
+                format_transform(transform)
+            ));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn from_save_string(contents: &str) -> Result<SavedRound, String> {
+        let mut lines = contents.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version "))
+            .and_then(|version| version.trim().parse::<u32>().ok());
+        if version != Some(SAVE_VERSION) {
+            return Err(format!(
+                "it's from a different version of the game (save version {}, expected {})",
+                version.map_or("unknown".to_string(), |version| version.to_string()),
+                SAVE_VERSION
+            ));
+        }
+
+        let mut level = None;
+        let mut day = None;
+        let mut score = None;
+        let mut round_length = None;
+        let mut time_remaining = None;
+        let mut models = None;
+        let mut combine = None;
+        let mut truck = None;
+        let mut combine_load = None;
+        let mut truck_load = None;
+        let mut header = None;
+        let mut fields = Vec::new();
+        let mut contracts = None;
+        let mut active_contracts = Vec::new();
+        let mut weather = None;
+        let mut herding_completed = None;
+        let mut dog = None;
+        let mut animals = Vec::new();
+        for (number, line) in lines.enumerate() {
+            let mut words = line.split_whitespace();
+            let parsed = match words.next() {
+                Some("level") => words.next().and_then(parse_level).map(|l| level = Some(l)),
+                Some("day") => words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .map(|d| day = Some(d)),
+                Some("score") => words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .map(|s| score = Some(s)),
+                // anything a Duration can't hold counts as damaged
+                Some("round_length") => words
+                    .next()
+                    .and_then(|w| w.parse::<f32>().ok())
+                    .filter(|t| (0.0..=game::MAX_ROUND_SECONDS).contains(t))
+                    .map(|t| round_length = Some(t)),
+                Some("time_remaining") => words
+                    .next()
+                    .and_then(|w| w.parse::<f32>().ok())
+                    .filter(|t| (0.0..=game::MAX_ROUND_SECONDS).contains(t))
+                    .map(|t| time_remaining = Some(t)),
+                Some("models") => words
+                    .next()
+                    .and_then(parse_model)
+                    .zip(words.next().and_then(parse_model))
+                    .map(|m| models = Some(m)),
+                Some("combine") => parse_body(words).map(|b| combine = Some(b)),
+                Some("truck") => parse_body(words).map(|b| truck = Some(b)),
+                Some("combine_load") => parse_load(words).map(|l| combine_load = Some(l)),
+                Some("truck_load") => parse_load(words).map(|l| truck_load = Some(l)),
+                Some("header") => words
+                    .next()
+                    .and_then(HeaderKind::from_name)
+                    .zip(words.next().and_then(|w| w.parse().ok()))
+                    .map(|h| header = Some(h)),
+                Some("field") => {
+                    fields.push(words.nth(1).unwrap_or_default().to_string());
+                    Some(())
+                }
+                Some("contracts") => parse_contract_totals(words).map(|c| contracts = Some(c)),
+                Some("contract") => parse_contract(words).map(|c| active_contracts.push(c)),
+                Some("weather") => parse_weather(words).map(|w| weather = Some(w)),
+                Some("herding_completed") => words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .map(|h| herding_completed = Some(h)),
+                Some("dog") => parse_body(words).map(|b| dog = Some(b)),
+                Some("animal") => parse_animal(words).map(|a| animals.push(a)),
+                Some(_) | None => Some(()),
+            };
+            if parsed.is_none() {
+                // the version line was taken off the top
+                return Err(format!("line {} is damaged", number + 2));
+            }
+        }
+
+        let missing = |what: &str| format!("it has no {}", what);
+        let (combine_model, truck_model) = models.ok_or_else(|| missing("vehicle models"))?;
+        let round_length = round_length.ok_or_else(|| missing("round length"))?;
+        let time_remaining = time_remaining.ok_or_else(|| missing("time"))?;
+        if time_remaining > round_length {
+            return Err("it has more time left than the round lasts".to_string());
+        }
+        let (header, header_raised) = header.ok_or_else(|| missing("header"))?;
+        let mut contracts = contracts.ok_or_else(|| missing("contracts"))?;
+        contracts.active = active_contracts;
+        Ok(SavedRound {
+            level: level.ok_or_else(|| missing("level"))?,
+            day: day.ok_or_else(|| missing("day"))?,
+            score: score.ok_or_else(|| missing("score"))?,
+            round_length,
+            time_remaining,
+            combine_model,
+            truck_model,
+            combine: combine.ok_or_else(|| missing("combine"))?,
+            truck: truck.ok_or_else(|| missing("truck"))?,
+            combine_load: combine_load.ok_or_else(|| missing("combine load"))?,
+            truck_load: truck_load.ok_or_else(|| missing("truck load"))?,
+            header,
+            header_raised,
+            fields,
+            contracts,
+            weather: weather.ok_or_else(|| missing("weather"))?,
+            herding_completed: herding_completed.ok_or_else(|| missing("herding objective"))?,
+            dog,
+            animals,
+        })
+    }
+
+    // a career round only fits the day it was saved on, as the day's takings are banked
+    // when it ends, and playing it again would bank them twice
+    fn check_day(&self, career: &Career) -> Result<(), String> {
+        if self.level == Level::Career && self.day != career.day {
+            return Err(format!(
+                "it was saved on day {} of the career, which is over",
+                self.day
+            ));
+        }
+        Ok(())
+    }
+
+    fn load(career: &Career) -> Result<SavedRound, String> {
+        let contents = read_save().ok_or_else(|| "there's no saved round".to_string())?;
+        let round = SavedRound::from_save_string(&contents)?;
+        round.check_day(career)?;
+        Ok(round)
+    }
+}
+
+// what F9 and the menu's resume button share: reads the save, and puts the machines back
+// as they were, so the next round is spawned with them
+#[derive(SystemParam)]
+pub struct RoundLoader<'w, 's> {
+    choice: ResMut<'w, VehicleChoice>,
+    header: ResMut<'w, HeaderChoice>,
+    pending: ResMut<'w, PendingRound>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> RoundLoader<'w, 's> {
+    pub fn load(&mut self, game: &mut game::Game, career: &Career) -> Result<(), String> {
+        let round = SavedRound::load(career)?;
+        game.level = round.level;
+        self.choice.combine = round.combine_model;
+        self.choice.truck = round.truck_model;
+        self.header.kind = round.header;
+        self.pending.round = Some(round);
+        Ok(())
+    }
+}
+
+pub fn has_saved_round() -> bool {
+    read_save().is_some()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_save() -> Option<String> {
+    std::fs::read_to_string(SAVE_FILE).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_save(contents: &str) -> Result<(), String> {
+    std::fs::write(SAVE_FILE, contents).map_err(|error| format!("{}: {}", SAVE_FILE, error))
+}
+
+// like the career, the web build has nowhere to keep it
+#[cfg(target_arch = "wasm32")]
+fn read_save() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_save(_contents: &str) -> Result<(), String> {
+    Err("there's nowhere to keep it in the browser".to_string())
+}
+
+type Bodies<'w, 's, F> = Query<'w, 's, (&'static Transform, &'static Velocity), F>;
+type BodiesMut<'w, 's, F> = Query<'w, 's, (&'static mut Transform, &'static mut Velocity), F>;
+type HeadersMut<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Header,
+        &'static mut CropCutter,
+        &'static mut Transform,
+    ),
+    (Without<Combine>, Without<Truck>, Without<Dog>),
+>;
+
+// the round's resources, as they're saved
+#[derive(SystemParam)]
+pub struct RoundState<'w, 's> {
+    game: Res<'w, game::Game>,
+    career: Res<'w, Career>,
+    choice: Res<'w, VehicleChoice>,
+    combine_storage: Res<'w, CombineStorage>,
+    truck_storage: Res<'w, TruckStorage>,
+    contracts: Res<'w, Contracts>,
+    weather: Res<'w, Weather>,
+    objective: Res<'w, HerdingObjective>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+// and everything out in the world
+#[derive(SystemParam)]
+pub struct RoundBodies<'w, 's> {
+    combines: Bodies<'w, 's, With<Combine>>,
+    trucks: Bodies<'w, 's, With<Truck>>,
+    dogs: Bodies<'w, 's, With<Dog>>,
+    animals: Query<'w, 's, (&'static AnimalKind, &'static Transform), With<Animal>>,
+    headers: Query<'w, 's, &'static Header>,
+    grids: Query<'w, 's, &'static CropGrid>,
+}
+
+pub fn save_round(
+    mut notice: ResMut<Notice>,
+    keyboard_input: Res<Input<KeyCode>>,
+    replay: Res<Replay>,
+    state: RoundState,
+    bodies: RoundBodies,
+) {
+    // the tutorial is short, and steps through things in order
+    if !keyboard_input.just_pressed(SAVE_KEY)
+        || state.game.level == Level::Tutorial
+        || replay.mode == ReplayMode::Playing
+    {
+        return;
+    }
+    let (combine, truck, header) = match (
+        bodies.combines.get_single(),
+        bodies.trucks.get_single(),
+        bodies.headers.get_single(),
+    ) {
+        (Ok(combine), Ok(truck), Ok(header)) => (combine, truck, header),
+        _ => return,
+    };
+    let game = &state.game;
+    let round = SavedRound {
+        level: game.level,
+        day: state.career.day,
+        score: game.score,
+        round_length: game.round_length.as_secs_f32(),
+        time_remaining: game.time_remaining.as_secs_f32(),
+        combine_model: state.choice.combine,
+        truck_model: state.choice.truck,
+        combine: BodyState::new(combine.0, combine.1),
+        truck: BodyState::new(truck.0, truck.1),
+        combine_load: state.combine_storage.contents,
+        truck_load: state.truck_storage.contents,
+        header: header.kind,
+        header_raised: header.raised,
+        fields: game
+            .fields
+            .iter()
+            .map(|field| {
+                bodies
+                    .grids
+                    .get(field.crops)
+                    .map(encode_plants)
+                    .unwrap_or_default()
+            })
+            .collect(),
+        contracts: state.contracts.clone(),
+        weather: WeatherState::new(&state.weather),
+        herding_completed: state.objective.completed,
+        dog: bodies
+            .dogs
+            .get_single()
+            .ok()
+            .map(|(transform, velocity)| BodyState::new(transform, velocity)),
+        animals: bodies
+            .animals
+            .iter()
+            .map(|(kind, transform)| (*kind, *transform))
+            .collect(),
+    };
+    match write_save(&round.to_save_string()) {
+        Ok(()) => notice.show("Round saved (F9 to load)"),
+        Err(error) => notice.warn(format!("Couldn't save the round: {}", error)),
+    }
+}
+
+// the round is played again from the start with the saved machines, via Rewinding to
+// clear the current one away, and restore_round then puts everything where it was
+pub fn load_round(
+    mut notice: ResMut<Notice>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game: ResMut<game::Game>,
+    career: Res<Career>,
+    mut loader: RoundLoader,
+    mut app_state: ResMut<State<game::GameState>>,
+    replay: Res<Replay>,
+) {
+    if !keyboard_input.just_pressed(LOAD_KEY) || replay.mode == ReplayMode::Playing {
+        return;
+    }
+    match loader.load(&mut game, &career) {
+        Ok(()) => {
+            keyboard_input.clear();
+            // a load wins over the round ending on the same frame
+            let _ = app_state.overwrite_set(game::GameState::Rewinding);
+            notice.show("Round loaded");
+        }
+        Err(error) => notice.warn(format!("Couldn't load the round: {}", error)),
+    }
+}
+
+// the round's resources, to be put back as they were saved
+#[derive(SystemParam)]
+pub struct RestoredState<'w, 's> {
+    game: ResMut<'w, game::Game>,
+    combine_storage: ResMut<'w, CombineStorage>,
+    truck_storage: ResMut<'w, TruckStorage>,
+    contracts: ResMut<'w, Contracts>,
+    weather: ResMut<'w, Weather>,
+    objective: ResMut<'w, HerdingObjective>,
+    replay: ResMut<'w, Replay>,
+    ghosts: ResMut<'w, Ghosts>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+// and everything out in the world
+#[derive(SystemParam)]
+pub struct RestoredBodies<'w, 's> {
+    combines: BodiesMut<'w, 's, With<Combine>>,
+    trucks: BodiesMut<'w, 's, (With<Truck>, Without<Combine>)>,
+    dogs: BodiesMut<'w, 's, (With<Dog>, Without<Combine>, Without<Truck>)>,
+    animals: Query<'w, 's, Entity, With<Animal>>,
+    headers: HeadersMut<'w, 's>,
+    grids: Query<'w, 's, &'static mut CropGrid>,
+}
+
+// waits for the round to be spawned, then moves everything back to where it was saved
+pub fn restore_round(
+    mut commands: Commands,
+    mut pending: ResMut<PendingRound>,
+    (mut meshes, mut materials): (ResMut<Assets<Mesh>>, ResMut<Assets<StandardMaterial>>),
+    mut state: RestoredState,
+    mut bodies: RestoredBodies,
+) {
+    if pending.round.is_none()
+        || bodies.combines.is_empty()
+        || bodies.trucks.is_empty()
+        || bodies.headers.is_empty()
+        || state
+            .game
+            .fields
+            .iter()
+            .any(|field| bodies.grids.get(field.crops).is_err())
+    {
+        return;
+    }
+    let round = pending.round.take().unwrap();
+
+    state.game.score = round.score;
+    state.game.round_length = Duration::from_secs_f32(round.round_length);
+    state.game.time_remaining = Duration::from_secs_f32(round.time_remaining);
+    if let Ok((mut transform, mut velocity)) = bodies.combines.get_single_mut() {
+        round.combine.restore(&mut transform, &mut velocity);
+    }
+    if let Ok((mut transform, mut velocity)) = bodies.trucks.get_single_mut() {
+        round.truck.restore(&mut transform, &mut velocity);
+    }
+    state.combine_storage.contents = round.combine_load;
+    state.truck_storage.contents = round.truck_load;
+    // the right kind was fitted when the combine was spawned, from the header choice
+    for (mut header, mut cutter, mut transform) in bodies.headers.iter_mut() {
+        set_raised(
+            &mut header,
+            &mut cutter,
+            &mut transform,
+            round.header_raised,
+        );
+    }
+    for (field, plants) in state.game.fields.iter().zip(round.fields.iter()) {
+        if let Ok(mut grid) = bodies.grids.get_mut(field.crops) {
+            decode_plants(plants, &mut grid);
+        }
+    }
+    *state.contracts = round.contracts;
+    round.weather.restore(&mut state.weather);
+    state.objective.completed = round.herding_completed;
+    if let (Ok((mut transform, mut velocity)), Some(dog)) =
+        (bodies.dogs.get_single_mut(), &round.dog)
+    {
+        dog.restore(&mut transform, &mut velocity);
+    }
+    // some may have been let loose from the console, so the flock's made up afresh
+    for entity in bodies.animals.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (kind, transform) in round.animals.iter() {
+        spawn_animal(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            *kind,
+            *transform,
+        );
+    }
+
+    // neither of these could play back a round that jumped partway through
+    state.replay.abandon();
+    state.ghosts.abandon();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crop_grid::PlantState;
+
+    // a couple of fields, part harvested and with a wheel track through the second
+    fn grids() -> Vec<CropGrid> {
+        let mut first = CropGrid::new(CropType::Wheat, Vec2::ZERO, 6, 4, 1);
+        let mut second = CropGrid::new(CropType::Barley, Vec2::ZERO, 5, 5, 2);
+        for column in 0..6 {
+            first.set_plant_state(column, 0, PlantState::Harvested);
+            first.set_plant_state(column, 1, PlantState::Harvested);
+        }
+        for row in 0..5 {
+            second.set_plant_state(2, row, PlantState::Squashed);
+        }
+        second.set_plant_state(4, 4, PlantState::Harvested);
+        vec![first, second]
+    }
+
+    fn plant_states(grid: &CropGrid) -> Vec<PlantState> {
+        (0..grid.rows)
+            .flat_map(|row| (0..grid.columns).map(move |column| (column, row)))
+            .map(|(column, row)| grid.plant_state(column, row))
+            .collect()
+    }
+
+    fn body(x: f32) -> BodyState {
+        BodyState {
+            transform: Transform::from_xyz(x, 0.5, -x),
+            linear: Vec3::new(1., 0., 2.),
+            angular: Vec3::new(0., 0.25, 0.),
+        }
+    }
+
+    fn round() -> SavedRound {
+        let mut combine_load = CropLoad::default();
+        combine_load.add(CropType::Wheat, 120);
+        combine_load.add(CropType::Canola, 30);
+        SavedRound {
+            level: Level::Career,
+            day: 3,
+            score: 1234,
+            round_length: 600.,
+            time_remaining: 87.5,
+            combine_model: Some(2),
+            truck_model: None,
+            combine: body(10.),
+            truck: body(-20.),
+            combine_load,
+            truck_load: CropLoad::default(),
+            header: HeaderKind::Wide,
+            header_raised: true,
+            fields: grids().iter().map(encode_plants).collect(),
+            contracts: Contracts {
+                active: vec![Contract {
+                    crop: CropType::Barley,
+                    amount: 300,
+                    delivered: 50,
+                    time_left: 42.5,
+                    reward: 600,
+                    penalty: 300,
+                }],
+                completed: 1,
+                failed: 2,
+                time_to_offer: 12.25,
+            },
+            weather: WeatherState {
+                current: WeatherKind::Rain,
+                next: WeatherKind::Storm,
+                time_to_change: 33.,
+                wetness: 0.4,
+            },
+            herding_completed: true,
+            dog: Some(body(5.)),
+            animals: vec![
+                (AnimalKind::Sheep, Transform::from_xyz(1., 0.1, 2.)),
+                (AnimalKind::Pig, Transform::from_xyz(-3., 0.1, 4.)),
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let saved = round().to_save_string();
+        let loaded = SavedRound::from_save_string(&saved).unwrap();
+        assert_eq!(loaded.to_save_string(), saved);
+        assert!(loaded.level == Level::Career);
+        assert_eq!(loaded.day, 3);
+        assert_eq!(loaded.score, 1234);
+        assert_eq!(loaded.round_length, 600.);
+        assert_eq!(loaded.time_remaining, 87.5);
+        assert_eq!(loaded.combine_model, Some(2));
+        assert_eq!(loaded.truck_model, None);
+        assert!(loaded.combine_load == round().combine_load);
+        assert_eq!(loaded.header, HeaderKind::Wide);
+        assert!(loaded.header_raised);
+        assert_eq!(loaded.contracts.active.len(), 1);
+        assert_eq!(loaded.contracts.active[0].crop, CropType::Barley);
+        assert_eq!(loaded.weather.next, WeatherKind::Storm);
+        assert!(loaded.herding_completed);
+        assert!(loaded.dog.is_some());
+        assert_eq!(loaded.animals[1].0, AnimalKind::Pig);
+    }
+
+    #[test]
+    fn plants_survive_save_and_load() {
+        let saved = round().to_save_string();
+        let loaded = SavedRound::from_save_string(&saved).unwrap();
+        assert_eq!(loaded.fields.len(), 2);
+        for (original, plants) in grids().iter().zip(loaded.fields.iter()) {
+            let mut restored = CropGrid::new(
+                original.crop_type,
+                Vec2::ZERO,
+                original.columns,
+                original.rows,
+                0,
+            );
+            decode_plants(plants, &mut restored);
+            assert_eq!(plant_states(&restored), plant_states(original));
+        }
+    }
+
+    #[test]
+    fn only_fits_the_day_it_was_saved_on() {
+        let mut career = Career::default();
+        career.day = 3;
+        assert!(round().check_day(&career).is_ok());
+        career.day = 4;
+        assert!(round().check_day(&career).is_err());
+        let mut harvest = round();
+        harvest.level = Level::Harvest;
+        assert!(harvest.check_day(&career).is_ok());
+    }
+
+    #[test]
+    fn wrong_version_is_rejected() {
+        let saved = round().to_save_string().replacen(
+            &format!("version {}", SAVE_VERSION),
+            &format!("version {}", SAVE_VERSION + 1),
+            1,
+        );
+        assert!(SavedRound::from_save_string(&saved).is_err());
+    }
+
+    #[test]
+    fn missing_version_is_rejected() {
+        let saved = round().to_save_string();
+        let without_version = saved.split_once('\n').unwrap().1;
+        assert!(SavedRound::from_save_string(without_version).is_err());
+        assert!(SavedRound::from_save_string("").is_err());
+    }
+
+    #[test]
+    fn time_out_of_range_is_damaged() {
+        let saved = round().to_save_string();
+        for time in ["inf", "NaN", "-1", "1e20"] {
+            let damaged = saved.replacen(
+                "time_remaining 87.5",
+                &format!("time_remaining {}", time),
+                1,
+            );
+            assert!(SavedRound::from_save_string(&damaged).is_err(), "{}", time);
+        }
+    }
+
+    #[test]
+    fn more_time_left_than_the_round_is_rejected() {
+        let saved = round()
+            .to_save_string()
+            .replacen("round_length 600", "round_length 60", 1);
+        assert!(SavedRound::from_save_string(&saved).is_err());
+    }
+
+    #[test]
+    fn damaged_line_is_reported() {
+        let saved = round().to_save_string();
+        let mut lines: Vec<&str> = saved.lines().collect();
+        let number = lines
+            .iter()
+            .position(|line| line.starts_with("weather "))
+            .unwrap();
+        lines[number] = "weather Drizzle Storm 33 0.4";
+        let error = SavedRound::from_save_string(&lines.join("\n"))
+            .err()
+            .unwrap();
+        assert_eq!(error, format!("line {} is damaged", number + 1));
+    }
+}
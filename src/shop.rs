@@ -0,0 +1,332 @@
+use crate::career::Career;
+use crate::game::GameState;
+use crate::menu::{spawn_button, text_style, ButtonSize, BUTTON_COLOR, HOVERED_COLOR};
+use crate::vehicles::Vehicle;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+const SOLD_OUT_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Upgrade {
+    Hopper,
+    TruckBed,
+    Engine,
+    Auger,
+    Tipper,
+}
+
+pub const UPGRADES: [Upgrade; 5] = [
+    Upgrade::Hopper,
+    Upgrade::TruckBed,
+    Upgrade::Engine,
+    Upgrade::Auger,
+    Upgrade::Tipper,
+];
+
+pub struct UpgradeInfo {
+    pub name: &'static str,
+    // as written in the save file
+    pub key: &'static str,
+    pub description: &'static str,
+    // the nth level costs n times this
+    pub base_cost: i32,
+    pub max_level: u32,
+}
+
+// indexed by Upgrade
+const UPGRADE_REGISTRY: [UpgradeInfo; 5] = [
+    UpgradeInfo {
+        name: "Bigger hopper",
+        key: "hopper",
+        description: "+100 combine capacity",
+        base_cost: 300,
+        max_level: 5,
+    },
+    UpgradeInfo {
+        name: "Truck extension",
+        key: "truck_bed",
+        description: "+300 truck capacity",
+        base_cost: 400,
+        max_level: 5,
+    },
+    UpgradeInfo {
+        name: "Engine tune",
+        key: "engine",
+        description: "+10% speed, reverse, acceleration and turning",
+        base_cost: 500,
+        max_level: 3,
+    },
+    UpgradeInfo {
+        name: "Faster auger",
+        key: "auger",
+        description: "+50 grain per second unloading",
+        base_cost: 250,
+        max_level: 4,
+    },
+    UpgradeInfo {
+        name: "Quicker tipper",
+        key: "tipper",
+        description: "+25% truck dump speed",
+        base_cost: 200,
+        max_level: 4,
+    },
+];
+
+impl Upgrade {
+    pub fn info(&self) -> &'static UpgradeInfo {
+        &UPGRADE_REGISTRY[*self as usize]
+    }
+
+    pub fn from_key(key: &str) -> Option<Upgrade> {
+        UPGRADES
+            .iter()
+            .copied()
+            .find(|upgrade| upgrade.info().key == key)
+    }
+}
+
+// how far each upgrade has been bought
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Upgrades {
+    levels: [u32; 5],
+}
+
+impl Upgrades {
+    pub fn level(&self, upgrade: Upgrade) -> u32 {
+        self.levels[upgrade as usize]
+    }
+
+    pub fn set_level(&mut self, upgrade: Upgrade, level: u32) {
+        self.levels[upgrade as usize] = level.min(upgrade.info().max_level);
+    }
+
+    // None once it's maxed out
+    pub fn next_cost(&self, upgrade: Upgrade) -> Option<i32> {
+        let level = self.level(upgrade);
+        if level >= upgrade.info().max_level {
+            None
+        } else {
+            Some(upgrade.info().base_cost * (level as i32 + 1))
+        }
+    }
+
+    pub fn combine_capacity(&self, base: i32) -> i32 {
+        base + 100 * self.level(Upgrade::Hopper) as i32
+    }
+
+    pub fn truck_capacity(&self, base: i32) -> i32 {
+        base + 300 * self.level(Upgrade::TruckBed) as i32
+    }
+
+    pub fn transfer_speed(&self, base: f32) -> f32 {
+        base + 50. * self.level(Upgrade::Auger) as f32
+    }
+
+    pub fn dump_speed(&self, base: f32) -> f32 {
+        base * (1. + 0.25 * self.level(Upgrade::Tipper) as f32)
+    }
+
+    pub fn apply_engine(&self, vehicle: &mut Vehicle) {
+        let factor = 1. + 0.1 * self.level(Upgrade::Engine) as f32;
+        vehicle.drive_speed *= factor;
+        vehicle.acceleration *= factor;
+        vehicle.max_reverse *= factor;
+        vehicle.turn_rate *= factor;
+    }
+}
+
+#[derive(Component)]
+pub struct ShopRoot {}
+
+#[derive(Component)]
+pub struct ShopCamera {}
+
+#[derive(Component)]
+pub struct UpgradeButton {
+    upgrade: Upgrade,
+}
+
+#[derive(Component)]
+pub struct BackButton {}
+
+#[derive(Component)]
+pub struct MoneyText {}
+
+fn button_label(career: &Career, upgrade: Upgrade) -> String {
+    let info = upgrade.info();
+    let level = career.upgrades.level(upgrade);
+    match career.upgrades.next_cost(upgrade) {
+        Some(cost) => format!(
+            "{} ({}/{}): {} - ${}",
+            info.name, level, info.max_level, info.description, cost
+        ),
+        None => format!("{} ({}/{}): sold out", info.name, level, info.max_level),
+    }
+}
+
+// wide enough for the engine's description
+const UPGRADE_BUTTON: ButtonSize = ButtonSize {
+    width: 760.,
+    height: 50.,
+    margin: 6.,
+    font_size: 24.,
+};
+
+const BACK_BUTTON: ButtonSize = ButtonSize {
+    width: 260.,
+    height: 50.,
+    margin: 16.,
+    font_size: 24.,
+};
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>, career: Res<Career>) {
+    let font_handle = asset_server.load("fonts/abel-regular.ttf");
+
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(ShopCamera {});
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgb(0.1, 0.2, 0.1).into(),
+            ..default()
+        })
+        .insert(ShopRoot {})
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Upgrade shop",
+                text_style(&font_handle, 56.),
+            ));
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    format!("${} to spend", career.money),
+                    text_style(&font_handle, 32.),
+                ))
+                .insert(MoneyText {});
+            parent.spawn_bundle(TextBundle::from_section(
+                "Upgrades are fitted for Career days only",
+                text_style(&font_handle, 24.),
+            ));
+
+            for upgrade in UPGRADES {
+                spawn_button(
+                    parent,
+                    &font_handle,
+                    &button_label(&career, upgrade),
+                    UPGRADE_BUTTON,
+                    button_color(&career, upgrade, Interaction::None),
+                    UpgradeButton { upgrade },
+                );
+            }
+            spawn_button(
+                parent,
+                &font_handle,
+                "Back (Esc)",
+                BACK_BUTTON,
+                BUTTON_COLOR,
+                BackButton {},
+            );
+        });
+}
+
+type ShopRoots<'w, 's> = Query<'w, 's, Entity, Or<(With<ShopRoot>, With<ShopCamera>)>>;
+
+pub fn teardown(mut commands: Commands, roots: ShopRoots) {
+    for entity in roots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn button_color(career: &Career, upgrade: Upgrade, interaction: Interaction) -> Color {
+    match (career.upgrades.next_cost(upgrade), interaction) {
+        (None, _) => SOLD_OUT_COLOR,
+        (_, Interaction::Hovered) => HOVERED_COLOR,
+        _ => BUTTON_COLOR,
+    }
+}
+
+type ClickedUpgrades<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Interaction,
+        &'static UpgradeButton,
+        &'static mut UiColor,
+    ),
+    (Changed<Interaction>, Without<BackButton>),
+>;
+
+// the shop's text that changes when something's bought
+#[derive(SystemParam)]
+pub struct ShopLabels<'w, 's> {
+    upgrade_buttons: Query<'w, 's, (&'static UpgradeButton, &'static Children)>,
+    texts: Query<'w, 's, &'static mut Text>,
+    money_texts: Query<'w, 's, Entity, With<MoneyText>>,
+}
+
+pub fn shop_input(
+    mut career: ResMut<Career>,
+    mut app_state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    // only on change, so holding the mouse down doesn't keep buying
+    mut clicked_buttons: ClickedUpgrades,
+    mut back_buttons: Query<(&Interaction, &mut UiColor), With<BackButton>>,
+    labels: ShopLabels,
+) {
+    let ShopLabels {
+        upgrade_buttons,
+        mut texts,
+        money_texts,
+    } = labels;
+    let mut bought = false;
+    for (interaction, button, mut color) in clicked_buttons.iter_mut() {
+        if *interaction == Interaction::Clicked {
+            let cost = career.upgrades.next_cost(button.upgrade);
+            if let Some(cost) = cost.filter(|cost| *cost <= career.money) {
+                career.money -= cost;
+                let level = career.upgrades.level(button.upgrade) + 1;
+                career.upgrades.set_level(button.upgrade, level);
+                bought = true;
+            }
+        }
+        color.0 = button_color(&career, button.upgrade, *interaction);
+    }
+
+    if bought {
+        career.save();
+        for (button, children) in upgrade_buttons.iter() {
+            for child in children.iter() {
+                if let Ok(mut text) = texts.get_mut(*child) {
+                    text.sections[0].value = button_label(&career, button.upgrade);
+                }
+            }
+        }
+        for entity in money_texts.iter() {
+            if let Ok(mut text) = texts.get_mut(entity) {
+                text.sections[0].value = format!("${} to spend", career.money);
+            }
+        }
+    }
+
+    let mut back = keyboard_input.just_pressed(KeyCode::Escape);
+    for (interaction, mut color) in back_buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => back = true,
+            Interaction::Hovered => color.0 = HOVERED_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+    if back {
+        keyboard_input.clear();
+        app_state.set(GameState::Menu).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use crate::animals::Animal;
+use crate::game::ScoreChangeEvent;
+use crate::harvest::{CropHarvestedEvent, CropSquashedEvent};
+
+use bevy::audio::AudioSink;
+use bevy::input::mouse::MouseButtonInput;
+use bevy::prelude::*;
+use heron::prelude::*;
+use rand::Rng;
+
+// bevy doesn't do positional audio yet, so sounds just get quieter with distance from
+// wherever the camera is looking
+const REFERENCE_DISTANCE: f32 = 40.;
+// a cut or crunch fires for every plant, so only play them this often (seconds)
+const CROP_SOUND_INTERVAL: f32 = 0.12;
+// chance per second of each animal making a noise
+const ANIMAL_CALL_CHANCE: f32 = 0.08;
+const VOLUME_STEP: f32 = 0.1;
+
+const IDLE_PITCH: f32 = 0.6;
+const FULL_SPEED_PITCH: f32 = 1.4;
+
+pub struct SoundSettings {
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub engine_volume: f32,
+    pub muted: bool,
+    // browsers refuse to start audio until someone has clicked or pressed a key
+    pub unlocked: bool,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        SoundSettings {
+            master_volume: 0.7,
+            effects_volume: 1.,
+            engine_volume: 0.5,
+            muted: false,
+            unlocked: !cfg!(target_arch = "wasm32"),
+        }
+    }
+}
+
+impl SoundSettings {
+    fn effects(&self) -> f32 {
+        if self.muted || !self.unlocked {
+            0.
+        } else {
+            self.master_volume * self.effects_volume
+        }
+    }
+
+    fn engines(&self) -> f32 {
+        if self.muted || !self.unlocked {
+            0.
+        } else {
+            self.master_volume * self.engine_volume
+        }
+    }
+}
+
+pub struct Sounds {
+    engine: Handle<AudioSource>,
+    cut: Handle<AudioSource>,
+    crunch: Handle<AudioSource>,
+    cash: Handle<AudioSource>,
+    cut_cooldown: f32,
+    crunch_cooldown: f32,
+}
+
+impl FromWorld for Sounds {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Sounds {
+            engine: asset_server.load("sounds/engine.wav"),
+            cut: asset_server.load("sounds/cut.wav"),
+            crunch: asset_server.load("sounds/crunch.wav"),
+            cash: asset_server.load("sounds/cash.wav"),
+            cut_cooldown: 0.,
+            crunch_cooldown: 0.,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct EngineSound {
+    pub top_speed: f32,
+    sink: Option<Handle<AudioSink>>,
+}
+
+impl EngineSound {
+    pub fn new(top_speed: f32) -> Self {
+        EngineSound {
+            top_speed,
+            sink: None,
+        }
+    }
+}
+
+// the point on the ground in the middle of the screen
+fn listener_position(cameras: &Query<&Transform, With<Camera3d>>) -> Vec3 {
+    cameras.get_single().map_or(Vec3::ZERO, |camera| {
+        let forward = camera.forward();
+        if forward.y < 0. {
+            camera.translation - forward * camera.translation.y / forward.y
+        } else {
+            camera.translation
+        }
+    })
+}
+
+fn attenuation(listener: Vec3, position: Vec3) -> f32 {
+    1. / (1. + listener.distance(position) / REFERENCE_DISTANCE)
+}
+
+pub fn sound_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_events: EventReader<MouseButtonInput>,
+    mut settings: ResMut<SoundSettings>,
+) {
+    if !settings.unlocked
+        && (keyboard_input.get_just_pressed().next().is_some() || mouse_events.iter().count() > 0)
+    {
+        settings.unlocked = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::M) {
+        settings.muted = !settings.muted;
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        settings.master_volume = (settings.master_volume - VOLUME_STEP).max(0.);
+    }
+    if keyboard_input.just_pressed(KeyCode::Equals) {
+        settings.master_volume = (settings.master_volume + VOLUME_STEP).min(1.);
+    }
+}
+
+pub fn engine_sounds(
+    audio: Res<Audio>,
+    sounds: Res<Sounds>,
+    settings: Res<SoundSettings>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    cameras: Query<&Transform, With<Camera3d>>,
+    mut engines: Query<(&mut EngineSound, &Transform, &Velocity)>,
+) {
+    let listener = listener_position(&cameras);
+    for (mut engine, transform, velocity) in engines.iter_mut() {
+        // don't start anything until the browser will let us
+        if engine.sink.is_none() && settings.unlocked {
+            let sink = audio.play_with_settings(
+                sounds.engine.clone(),
+                PlaybackSettings::LOOP.with_volume(0.),
+            );
+            engine.sink = Some(audio_sinks.get_handle(sink));
+        }
+        if let Some(sink) = engine.sink.as_ref().and_then(|sink| audio_sinks.get(sink)) {
+            let speed_fraction = (velocity.linear.length() / engine.top_speed).min(1.);
+            sink.set_speed(IDLE_PITCH + (FULL_SPEED_PITCH - IDLE_PITCH) * speed_fraction);
+            sink.set_volume(settings.engines() * attenuation(listener, transform.translation));
+            if sink.is_paused() {
+                sink.play();
+            }
+        }
+    }
+}
+
+pub fn stop_engines(audio_sinks: Res<Assets<AudioSink>>, engines: Query<&EngineSound>) {
+    for engine in engines.iter() {
+        if let Some(sink) = engine.sink.as_ref().and_then(|sink| audio_sinks.get(sink)) {
+            sink.pause();
+        }
+    }
+}
+
+pub fn crop_sounds(
+    audio: Res<Audio>,
+    mut sounds: ResMut<Sounds>,
+    settings: Res<SoundSettings>,
+    time: Res<Time>,
+    cameras: Query<&Transform, With<Camera3d>>,
+    mut crop_harvested_events: EventReader<CropHarvestedEvent>,
+    mut crop_squashed_events: EventReader<CropSquashedEvent>,
+) {
+    let listener = listener_position(&cameras);
+    sounds.cut_cooldown -= time.delta_seconds();
+    sounds.crunch_cooldown -= time.delta_seconds();
+
+    if let Some(event) = crop_harvested_events.iter().last() {
+        if sounds.cut_cooldown <= 0. {
+            audio.play_with_settings(
+                sounds.cut.clone(),
+                PlaybackSettings::ONCE
+                    .with_volume(settings.effects() * attenuation(listener, event.position)),
+            );
+            sounds.cut_cooldown = CROP_SOUND_INTERVAL;
+        }
+    }
+    if let Some(event) = crop_squashed_events.iter().last() {
+        if sounds.crunch_cooldown <= 0. {
+            audio.play_with_settings(
+                sounds.crunch.clone(),
+                PlaybackSettings::ONCE
+                    .with_volume(settings.effects() * attenuation(listener, event.position)),
+            );
+            sounds.crunch_cooldown = CROP_SOUND_INTERVAL;
+        }
+    }
+}
+
+pub fn animal_sounds(
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    settings: Res<SoundSettings>,
+    time: Res<Time>,
+    cameras: Query<&Transform, With<Camera3d>>,
+    animals: Query<(&Animal, &Transform)>,
+) {
+    let listener = listener_position(&cameras);
+    let mut rng = rand::thread_rng();
+    for (animal, transform) in animals.iter() {
+        if animal.call.is_empty() || rng.gen::<f32>() > ANIMAL_CALL_CHANCE * time.delta_seconds() {
+            continue;
+        }
+        audio.play_with_settings(
+            asset_server.load(animal.call),
+            PlaybackSettings::ONCE
+                .with_volume(settings.effects() * attenuation(listener, transform.translation))
+                .with_speed(rng.gen_range(0.9..1.1)),
+        );
+    }
+}
+
+pub fn score_sounds(
+    audio: Res<Audio>,
+    sounds: Res<Sounds>,
+    settings: Res<SoundSettings>,
+    mut score_change_events: EventReader<ScoreChangeEvent>,
+) {
+    if score_change_events.iter().any(|event| event.amount > 0) {
+        audio.play_with_settings(
+            sounds.cash.clone(),
+            PlaybackSettings::ONCE.with_volume(settings.effects()),
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use crate::drop_zone::DropZone;
+use crate::game;
+use crate::game::{GameState, Level, ScoreChangeEvent};
+use crate::harvest::CropHarvestedEvent;
+use crate::vehicles::{Combine, Truck, TruckStorage, TRANSFER_DISTANCE};
+
+use bevy::prelude::*;
+
+// how far the combine has to go before it counts as having driven
+const DRIVE_DISTANCE: f32 = 10.;
+const PLANTS_TO_HARVEST: usize = 40;
+const MARKER_HEIGHT: f32 = 8.;
+const MARKER_BOB: f32 = 1.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TutorialStep {
+    Drive,
+    Harvest,
+    MeetTruck,
+    Unload,
+    Done,
+}
+
+impl TutorialStep {
+    fn instructions(&self) -> String {
+        match self {
+            TutorialStep::Drive => "Drive the combine with W A S D".to_string(),
+            TutorialStep::Harvest => format!(
+                "Drive into the wheat field through its gate and harvest {} plants\n\
+                 (R raises and lowers the header)",
+                PLANTS_TO_HARVEST
+            ),
+            TutorialStep::MeetTruck => format!(
+                "Drive the truck with the arrow keys to within {} units of the combine\n\
+                 to take its grain",
+                TRANSFER_DISTANCE
+            ),
+            TutorialStep::Unload => {
+                "Drive the truck into the red drop zone and hold Space to sell the grain"
+                    .to_string()
+            }
+            TutorialStep::Done => "That's it! Press Enter to finish".to_string(),
+        }
+    }
+}
+
+pub struct Tutorial {
+    pub step: TutorialStep,
+    start: Option<Vec3>,
+    plants_harvested: usize,
+}
+
+impl Default for Tutorial {
+    fn default() -> Self {
+        Tutorial {
+            step: TutorialStep::Drive,
+            start: None,
+            plants_harvested: 0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TutorialText {}
+
+// bobs above whatever the current step is about
+#[derive(Component)]
+pub struct TutorialMarker {}
+
+pub fn setup(
+    mut commands: Commands,
+    mut tutorial: ResMut<Tutorial>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    game: Res<game::Game>,
+    asset_server: Res<AssetServer>,
+) {
+    *tutorial = Tutorial::default();
+    if game.level != Level::Tutorial {
+        return;
+    }
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                TutorialStep::Drive.instructions(),
+                TextStyle {
+                    font: asset_server.load("fonts/abel-regular.ttf"),
+                    font_size: 30.0,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(30.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(TutorialText {});
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: 1.,
+                subdivisions: 2,
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::YELLOW,
+                unlit: true,
+                ..default()
+            }),
+            ..default()
+        })
+        .insert(TutorialMarker {});
+}
+
+pub fn advance_tutorial(
+    mut tutorial: ResMut<Tutorial>,
+    game: Res<game::Game>,
+    truck_storage: Res<TruckStorage>,
+    combines: Query<&Transform, With<Combine>>,
+    mut crop_harvested_events: EventReader<CropHarvestedEvent>,
+    mut score_change_events: EventReader<ScoreChangeEvent>,
+    mut texts: Query<&mut Text, With<TutorialText>>,
+) {
+    if game.level != Level::Tutorial {
+        return;
+    }
+    // read everything every frame, so old events don't count towards a later step
+    let plants_cut = crop_harvested_events.iter().count();
+    let scored = score_change_events.iter().any(|event| event.amount > 0);
+
+    let step = tutorial.step;
+    match step {
+        TutorialStep::Drive => {
+            if let Ok(transform) = combines.get_single() {
+                let start = *tutorial.start.get_or_insert(transform.translation);
+                if start.distance(transform.translation) > DRIVE_DISTANCE {
+                    tutorial.step = TutorialStep::Harvest;
+                }
+            }
+        }
+        TutorialStep::Harvest => {
+            tutorial.plants_harvested += plants_cut;
+            if tutorial.plants_harvested >= PLANTS_TO_HARVEST {
+                tutorial.step = TutorialStep::MeetTruck;
+            }
+        }
+        TutorialStep::MeetTruck => {
+            if truck_storage.contents.total() > 0 {
+                tutorial.step = TutorialStep::Unload;
+            }
+        }
+        TutorialStep::Unload => {
+            if scored {
+                tutorial.step = TutorialStep::Done;
+            }
+        }
+        TutorialStep::Done => {}
+    }
+
+    if tutorial.step != step {
+        if let Ok(mut text) = texts.get_single_mut() {
+            text.sections[0].value = tutorial.step.instructions();
+        }
+    }
+}
+
+pub fn update_tutorial_marker(
+    tutorial: Res<Tutorial>,
+    game: Res<game::Game>,
+    time: Res<Time>,
+    combines: Query<&Transform, (With<Combine>, Without<TutorialMarker>)>,
+    trucks: Query<&Transform, (With<Truck>, Without<TutorialMarker>)>,
+    drop_zones: Query<&Transform, (With<DropZone>, Without<TutorialMarker>)>,
+    mut markers: Query<(&mut Transform, &mut Visibility), With<TutorialMarker>>,
+) {
+    let target = match tutorial.step {
+        TutorialStep::Drive => combines.get_single().ok().map(|t| t.translation),
+        TutorialStep::Harvest => game
+            .fields
+            .first()
+            .map(|field| Vec3::new(field.position.x, 0., field.position.y)),
+        TutorialStep::MeetTruck => trucks.get_single().ok().map(|t| t.translation),
+        TutorialStep::Unload => drop_zones.get_single().ok().map(|t| t.translation),
+        TutorialStep::Done => None,
+    };
+    for (mut transform, mut visibility) in markers.iter_mut() {
+        visibility.is_visible = target.is_some();
+        if let Some(target) = target {
+            let bob = (time.seconds_since_startup() as f32 * 3.).sin() * MARKER_BOB;
+            transform.translation = Vec3::new(target.x, MARKER_HEIGHT + bob, target.z);
+        }
+    }
+}
+
+pub fn finish_tutorial(
+    tutorial: Res<Tutorial>,
+    game: Res<game::Game>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    if game.level == Level::Tutorial
+        && tutorial.step == TutorialStep::Done
+        && keyboard_input.just_pressed(KeyCode::Return)
+    {
+        // the same press would take us straight back out of the results screen
+        keyboard_input.clear();
+        // the timer may have run out on the same frame, and already be heading there
+        let _ = app_state.set(GameState::GameOver);
+    }
+}
+
+type Leftovers<'w, 's> = Query<'w, 's, Entity, Or<(With<TutorialText>, With<TutorialMarker>)>>;
+
+pub fn teardown(mut commands: Commands, leftovers: Leftovers) {
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn();
+    }
+}
@@ -1,193 +1,518 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use std::marker::PhantomData;
 
+use crate::career;
+use crate::contracts;
+use crate::daylight;
+use crate::drop_zone;
+use crate::game;
+use crate::ghost;
+use crate::replay;
+use crate::rng;
 use crate::vehicles;
-use crate::{game, RigidBody};
+use crate::weather;
 
 #[derive(Component)]
 pub struct ScoreText {}
 
 #[derive(Component)]
-pub struct StorageText {}
+pub struct StorageText {
+    store: Store,
+}
 
 #[derive(Component)]
 pub struct TimerText {}
 
-pub fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    game: Res<game::Game>,
-    combine_storage: Res<vehicles::CombineStorage>,
-    truck_storage: Res<vehicles::TruckStorage>,
-) {
-    let font_handle = asset_server.load("fonts/abel-regular.ttf");
+#[derive(Component)]
+pub struct WeatherText {}
 
-    commands
-        .spawn_bundle(TextBundle::from_sections([
-            TextSection::new(
-                "Score: ",
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 40.0,
-                    // Alpha channel of the color controls transparency.
-                    color: Color::rgba(1.0, 1.0, 1.0, 0.2),
-                },
-            ),
-            TextSection::new(
-                "0",
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 40.0,
-                    color: Color::rgba(1., 1., 1., 0.2),
-                },
-            ),
-        ]))
-        .insert(ScoreText {});
+#[derive(Component)]
+pub struct ContractsText {}
 
-    commands
+// how the score compares with the best run's ghost at the same point
+#[derive(Component)]
+pub struct GhostText {}
+
+#[derive(Component)]
+pub struct HudRoot {}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Store {
+    Combine,
+    Truck,
+}
+
+#[derive(Component)]
+pub struct StorageBar {
+    store: Store,
+}
+
+#[derive(Component)]
+pub struct PromptText {}
+
+// seconds a notice stays up
+const NOTICE_TIME: f32 = 3.;
+
+// something the game has to tell the player, such as how saving went; shown in place of
+// the prompt for a few seconds
+#[derive(Default)]
+pub struct Notice {
+    text: String,
+    color: Color,
+    time_left: f32,
+}
+
+impl Notice {
+    pub fn show(&mut self, text: impl Into<String>) {
+        self.set(text.into(), HUD_TEXT_COLOR);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.set(text.into(), Color::ORANGE);
+    }
+
+    fn set(&mut self, text: String, color: Color) {
+        self.text = text;
+        self.color = color;
+        self.time_left = NOTICE_TIME;
+    }
+}
+
+const HUD_TEXT_COLOR: Color = Color::rgba(1., 1., 1., 0.9);
+const PANEL_COLOR: Color = Color::rgba(0., 0., 0., 0.45);
+const BAR_WIDTH: f32 = 220.;
+const BAR_HEIGHT: f32 = 14.;
+// fill fraction where the combine's bar goes amber, then red
+const HOPPER_WARNING: f32 = 0.75;
+const HOPPER_FULL: f32 = 0.95;
+
+fn panel(position: UiRect<Val>) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position,
+            flex_direction: FlexDirection::ColumnReverse,
+            padding: UiRect::all(Val::Px(8.)),
+            ..default()
+        },
+        color: PANEL_COLOR.into(),
+        ..default()
+    }
+}
+
+fn text_style(font: &Handle<Font>, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: font.clone(),
+        font_size,
+        color: HUD_TEXT_COLOR,
+    }
+}
+
+fn storage_row(parent: &mut ChildBuilder, font: &Handle<Font>, label: &str, store: Store) {
+    parent
         .spawn_bundle(TextBundle::from_sections([
-            TextSection::new(
-                "Combine: ",
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 30.0,
-                    color: Color::rgba(1.0, 1.0, 1.0, 0.2),
-                },
-            ),
-            TextSection::new(
-                format!("0/{}", combine_storage.capacity),
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 40.0,
-                    color: Color::rgba(1., 1., 1., 0.2),
-                },
-            ),
-            TextSection::new(
-                "\nTruck: ",
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 30.0,
-                    color: Color::rgba(1.0, 1.0, 1.0, 0.2),
+            TextSection::new(label, text_style(font, 24.)),
+            TextSection::new("", text_style(font, 24.)),
+        ]))
+        .insert(StorageText { store });
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                margin: UiRect {
+                    bottom: Val::Px(6.),
+                    ..default()
                 },
-            ),
-            TextSection::new(
-                format!("0/{}", truck_storage.capacity),
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 40.0,
-                    color: Color::rgba(1., 1., 1., 0.2),
+                ..default()
+            },
+            color: Color::rgba(1., 1., 1., 0.15).into(),
+            ..default()
+        })
+        .with_children(|bar| {
+            bar.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                    ..default()
                 },
-            ),
-        ]))
-        .insert(StorageText {});
+                color: Color::GREEN.into(),
+                ..default()
+            })
+            .insert(StorageBar { store });
+        });
+}
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>, game: Res<game::Game>) {
+    let font_handle = asset_server.load("fonts/abel-regular.ttf");
 
     commands
-        .spawn_bundle(TextBundle::from_sections([
-            TextSection::new(
-                "Time left: ",
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 40.0,
-                    // Alpha channel of the color controls transparency.
-                    color: Color::rgba(1.0, 1.0, 1.0, 0.2),
-                },
-            ),
-            TextSection::new(
-                format!(
-                    "{}:{}",
-                    game.time_remaining.as_secs() / 60,
-                    game.time_remaining.as_secs() % 60
-                ),
-                TextStyle {
-                    font: font_handle.clone(),
-                    font_size: 40.0,
-                    color: Color::rgba(1., 1., 1., 0.2),
-                },
-            ),
-        ]))
-        .insert(TimerText {});
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(HudRoot {})
+        .with_children(|parent| {
+            // score and clock, top left
+            parent
+                .spawn_bundle(panel(UiRect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..default()
+                }))
+                .with_children(|panel| {
+                    panel
+                        .spawn_bundle(TextBundle::from_sections([
+                            TextSection::new("Score: ", text_style(&font_handle, 40.)),
+                            TextSection::new("0", text_style(&font_handle, 40.)),
+                        ]))
+                        .insert(ScoreText {});
+                    panel
+                        .spawn_bundle(TextBundle::from_sections([
+                            TextSection::new("Time left: ", text_style(&font_handle, 30.)),
+                            TextSection::new(
+                                format!(
+                                    "{}:{:02}",
+                                    game.time_remaining.as_secs() / 60,
+                                    game.time_remaining.as_secs() % 60
+                                ),
+                                text_style(&font_handle, 30.),
+                            ),
+                            TextSection::new("", text_style(&font_handle, 24.)),
+                        ]))
+                        .insert(TimerText {});
+                    panel
+                        .spawn_bundle(TextBundle::from_section("", text_style(&font_handle, 24.)))
+                        .insert(GhostText {});
+                });
+
+            // weather and contracts, top right
+            parent
+                .spawn_bundle(panel(UiRect {
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..default()
+                }))
+                .with_children(|panel| {
+                    panel
+                        .spawn_bundle(TextBundle::from_sections([
+                            TextSection::new("Weather: ", text_style(&font_handle, 24.)),
+                            TextSection::new("", text_style(&font_handle, 24.)),
+                        ]))
+                        .insert(WeatherText {});
+                    if game.level.has_contracts() {
+                        panel
+                            .spawn_bundle(TextBundle::from_sections([
+                                TextSection::new("Contracts:", text_style(&font_handle, 24.)),
+                                TextSection::new("", text_style(&font_handle, 20.)),
+                            ]))
+                            .insert(ContractsText {});
+                    }
+                });
+
+            // storage, bottom left
+            parent
+                .spawn_bundle(panel(UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..default()
+                }))
+                .with_children(|panel| {
+                    storage_row(panel, &font_handle, "Combine: ", Store::Combine);
+                    storage_row(panel, &font_handle, "Truck: ", Store::Truck);
+                });
+
+            // warnings and prompts, bottom middle
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            bottom: Val::Px(40.),
+                            ..default()
+                        },
+                        size: Size::new(Val::Percent(100.), Val::Auto),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn_bundle(TextBundle::from_section("", text_style(&font_handle, 32.)))
+                        .insert(PromptText {});
+                });
+        });
 }
 
-pub fn update_ui_score(game: Res<game::Game>, mut query: Query<&mut Text, With<ScoreText>>) {
+pub fn update_ui_score(
+    game: Res<game::Game>,
+    career: Res<career::Career>,
+    mut query: Query<&mut Text, With<ScoreText>>,
+) {
     if let Ok(mut text) = query.get_single_mut() {
-        text.sections[1].value = format!("{}", game.score);
+        text.sections[1].value = if game.level == game::Level::Career {
+            format!(
+                "{} (day {}, ${} banked)",
+                game.score, career.day, career.money
+            )
+        } else {
+            format!("{}", game.score)
+        };
     } else {
         println!("getting the thing failed :(")
     }
 }
 
+fn fill_color(fraction: f32) -> Color {
+    if fraction >= HOPPER_FULL {
+        Color::RED
+    } else if fraction >= HOPPER_WARNING {
+        Color::ORANGE
+    } else {
+        Color::GREEN
+    }
+}
+
 pub fn update_contents(
-    game: Res<game::Game>,
     combine_storage: Res<vehicles::CombineStorage>,
     truck_storage: Res<vehicles::TruckStorage>,
-    mut query: Query<&mut Text, With<StorageText>>,
+    mut texts: Query<(&mut Text, &StorageText)>,
+    mut bars: Query<(&mut Style, &mut UiColor, &StorageBar)>,
 ) {
-    if let Ok(mut text) = query.get_single_mut() {
-        text.sections[1].value =
-            format!("{}/{}", combine_storage.contents, combine_storage.capacity);
-        text.sections[3].value = format!("{}/{}", truck_storage.contents, truck_storage.capacity);
+    let fraction = |total: i32, capacity: i32| (total as f32 / capacity.max(1) as f32).min(1.);
+    let combine_fraction = fraction(combine_storage.contents.total(), combine_storage.capacity);
+    let truck_fraction = fraction(truck_storage.contents.total(), truck_storage.capacity);
+
+    for (mut text, storage_text) in texts.iter_mut() {
+        text.sections[1].value = match storage_text.store {
+            Store::Combine => format!(
+                "{}/{}",
+                combine_storage.contents.total(),
+                combine_storage.capacity
+            ),
+            Store::Truck => format!(
+                "{}/{} (worth {})",
+                truck_storage.contents.total(),
+                truck_storage.capacity,
+                truck_storage.contents.value()
+            ),
+        };
+    }
+    for (mut style, mut color, bar) in bars.iter_mut() {
+        let fraction = match bar.store {
+            Store::Combine => combine_fraction,
+            Store::Truck => truck_fraction,
+        };
+        style.size.width = Val::Percent(100. * fraction);
+        color.0 = fill_color(fraction);
     }
 }
 
-pub fn update_time(game: Res<game::Game>, mut query: Query<&mut Text, With<TimerText>>) {
+pub fn update_prompt(
+    mut notice: ResMut<Notice>,
+    time: Res<Time>,
+    combine_storage: Res<vehicles::CombineStorage>,
+    truck_storage: Res<vehicles::TruckStorage>,
+    drop_zones: Query<&drop_zone::DropZone>,
+    mut query: Query<&mut Text, With<PromptText>>,
+) {
+    notice.time_left -= time.delta_seconds();
     if let Ok(mut text) = query.get_single_mut() {
-        let remaining = game.time_remaining.as_secs();
-        if remaining % 60 < 10 {
-            text.sections[1].value = format!(
-                "{}:0{}",
-                game.time_remaining.as_secs() / 60,
-                game.time_remaining.as_secs() % 60
-            );
+        if notice.time_left > 0. {
+            let section = &mut text.sections[0];
+            section.value = notice.text.clone();
+            section.style.color = notice.color;
+            return;
+        }
+
+        let in_zone = drop_zones.get_single().is_ok_and(|zone| {
+            (zone.combine_in_zone && combine_storage.contents.total() > 0)
+                || (zone.truck_in_zone && truck_storage.contents.total() > 0)
+        });
+        let combine_fraction =
+            combine_storage.contents.total() as f32 / combine_storage.capacity.max(1) as f32;
+
+        let section = &mut text.sections[0];
+        if in_zone {
+            section.value = "Press Space to unload".to_string();
+            section.style.color = HUD_TEXT_COLOR;
+        } else if combine_fraction >= HOPPER_FULL {
+            section.value = "Hopper full! Bring the truck alongside".to_string();
+            section.style.color = Color::RED;
+        } else if combine_fraction >= HOPPER_WARNING {
+            section.value = "Hopper nearly full".to_string();
+            section.style.color = Color::ORANGE;
         } else {
-            text.sections[1].value = format!(
-                "{}:{}",
-                game.time_remaining.as_secs() / 60,
-                game.time_remaining.as_secs() % 60
-            );
+            section.value.clear();
         }
+    }
+}
+
+pub fn update_time(
+    game: Res<game::Game>,
+    time_of_day: Res<daylight::TimeOfDay>,
+    mut query: Query<&mut Text, With<TimerText>>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        text.sections[2].value = format!("  ({})", time_of_day.clock());
+        let remaining = game.time_remaining.as_secs();
+        text.sections[1].value = format!("{}:{:02}", remaining / 60, remaining % 60);
 
         if remaining < 10 {
-            text.sections[1].style.font_size = 40. + ((10 - remaining) as f32 * 2.);
+            text.sections[1].style.font_size = 30. + ((10 - remaining) as f32 * 2.);
+            text.sections[1].style.color = Color::RED;
         }
     }
 }
 
-pub fn display_final_score(
-    mut commands: Commands,
-    game: Res<game::Game>,
-    mut score_text: Query<(Entity, &Text), With<ScoreText>>,
-    mut storage_text: Query<(Entity, &Text), With<StorageText>>,
-    mut timer_text: Query<(Entity, &TimerText), With<TimerText>>,
-    mut asset_server: ResMut<AssetServer>,
+pub fn update_weather_forecast(
+    weather: Res<weather::Weather>,
+    mut query: Query<&mut Text, With<WeatherText>>,
 ) {
-    if let Ok((entity, text)) = score_text.get_single_mut() {
-        commands.entity(entity).despawn();
+    if let Ok(mut text) = query.get_single_mut() {
+        let seconds = weather.time_to_change.max(0.) as u32;
+        text.sections[1].value = format!(
+            "{}{}, {} in {}:{:02}",
+            weather.current.name(),
+            if weather.crop_wet() {
+                " (too wet to harvest)"
+            } else {
+                ""
+            },
+            weather.next.name(),
+            seconds / 60,
+            seconds % 60
+        );
+    }
+}
+
+pub fn update_ghost_delta(
+    ghosts: Res<ghost::Ghosts>,
+    mut query: Query<&mut Text, With<GhostText>>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        let section = &mut text.sections[0];
+        match ghosts.score_delta {
+            Some(delta) => {
+                section.value = format!("{:+} vs best run", delta);
+                section.style.color = if delta >= 0 {
+                    Color::GREEN
+                } else {
+                    Color::ORANGE
+                };
+            }
+            None => section.value.clear(),
+        }
     }
-    if let Ok((entity, storage)) = storage_text.get_single_mut() {
-        commands.entity(entity).despawn();
+}
+
+pub fn update_contracts(
+    contracts: Res<contracts::Contracts>,
+    mut query: Query<&mut Text, With<ContractsText>>,
+) {
+    if let Ok(mut text) = query.get_single_mut() {
+        text.sections[1].value = if contracts.active.is_empty() {
+            "\n  none yet".to_string()
+        } else {
+            contracts
+                .active
+                .iter()
+                .map(|contract| format!("\n  {}", contract.describe()))
+                .collect()
+        };
     }
-    if let Ok((entity, timer_text)) = timer_text.get_single_mut() {
-        commands.entity(entity).despawn();
+}
+
+// everything the game over screen reports on
+#[derive(SystemParam)]
+pub struct RoundResults<'w, 's> {
+    game: Res<'w, game::Game>,
+    career: Res<'w, career::Career>,
+    contracts: Res<'w, contracts::Contracts>,
+    replay: Res<'w, replay::Replay>,
+    rng: Res<'w, rng::GameRng>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+pub fn display_final_score(
+    mut commands: Commands,
+    results: RoundResults,
+    huds: Query<Entity, With<HudRoot>>,
+    asset_server: Res<AssetServer>,
+) {
+    let RoundResults {
+        game,
+        career,
+        contracts,
+        replay,
+        rng,
+        ..
+    } = results;
+    for hud in huds.iter() {
+        commands.entity(hud).despawn_recursive();
     }
 
     let font_handle = asset_server.load("fonts/abel-regular.ttf");
 
-    commands.spawn_bundle(TextBundle::from_sections([
-        TextSection::new(
-            "Game Over\nfinal score: ",
-            TextStyle {
-                font: font_handle.clone(),
-                font_size: 40.0,
-                // Alpha channel of the color controls transparency.
-                color: Color::rgba(1.0, 1.0, 1.0, 0.2),
-            },
-        ),
-        TextSection::new(
-            format!("{}", game.score),
-            TextStyle {
-                font: font_handle.clone(),
-                font_size: 40.0,
-                color: Color::rgba(1., 1., 1., 0.2),
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
             },
-        ),
-    ]));
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(panel(UiRect::default()))
+                .with_children(|panel| {
+                    let (heading, result) = if game.level == game::Level::Career {
+                        (
+                            format!("Day {} over\nearned: ", career.day - 1),
+                            format!("${} (${} in the bank)", game.score, career.money),
+                        )
+                    } else {
+                        (
+                            "Game Over\nfinal score: ".to_string(),
+                            format!("{}", game.score),
+                        )
+                    };
+                    panel.spawn_bundle(TextBundle::from_sections([
+                        TextSection::new(heading, text_style(&font_handle, 40.)),
+                        TextSection::new(result, text_style(&font_handle, 40.)),
+                        TextSection::new(
+                            if game.level.has_contracts() {
+                                format!(
+                                    "\ncontracts: {} met, {} missed",
+                                    contracts.completed, contracts.failed
+                                )
+                            } else {
+                                String::new()
+                            },
+                            text_style(&font_handle, 30.),
+                        ),
+                        TextSection::new(
+                            if replay.has_tape() {
+                                "\n\nPress R to watch the replay, or Enter to return to the menu"
+                            } else {
+                                "\n\nPress Enter to return to the menu"
+                            },
+                            text_style(&font_handle, 24.),
+                        ),
+                        TextSection::new(
+                            format!("\n(seed {})", rng.seed()),
+                            text_style(&font_handle, 18.),
+                        ),
+                    ]));
+                });
+        });
 }
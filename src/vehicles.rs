@@ -1,17 +1,64 @@
+use crate::animals;
+use crate::autopilot;
+use crate::career::Career;
+use crate::crop_grid;
+use crate::daylight;
+use crate::dealership::{VehicleCatalogue, VehicleChoice, VehicleClass, VehicleModel};
 use crate::game;
+use crate::game::Level;
 use crate::harvest;
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_6, PI};
+use crate::header;
+use crate::minimap;
+use crate::navigation;
+use crate::particles;
+use crate::shop::Upgrades;
+use crate::sound;
+use std::f32::consts::FRAC_PI_4;
+use std::marker::PhantomData;
 
-use bevy::gltf::GltfMesh;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use heron::prelude::*;
 
+pub const TRANSFER_DISTANCE: f32 = 15.;
+// how hard to steer for a given heading error
+const STEERING_GAIN: f32 = 2.;
+// per second
+const DRAG_RECOVERY: f32 = 0.5;
+// fractions lost on fully waterlogged ground
+const WET_GRIP_LOSS: f32 = 0.6;
+const WET_SPEED_LOSS: f32 = 0.3;
+
 #[derive(Default)]
 pub struct Vehicle {
     pub drive_speed: f32,
     pub turn_rate: f32,
     pub acceleration: f32,
     pub max_reverse: f32,
+    pub wheelbase: f32,
+    pub max_steering_angle: f32,
+    pub lateral_grip: f32,
+    pub braking: f32,
+    pub steering_angle: f32,
+    // top speed is divided by 1 + drag, e.g. while cutting heavy crops
+    pub drag: f32,
+    // steering speed is divided by 1 + turn_penalty, e.g. for a heavy header
+    pub turn_penalty: f32,
+    // something is in the way in front, so no driving forwards
+    pub blocked_ahead: bool,
+    // how wet the ground is, from 0 to 1; costs grip and top speed
+    pub wetness: f32,
+}
+
+impl Vehicle {
+    pub fn turning_radius(&self) -> f32 {
+        self.wheelbase / self.max_steering_angle.tan()
+    }
+}
+
+// grain moved from the combine's hopper into the truck
+pub struct GrainTransferEvent {
+    pub amount: i32,
 }
 
 #[derive(Component)]
@@ -23,19 +70,70 @@ pub struct Combine {
 #[derive(Component)]
 pub struct CombineStorage {
     pub capacity: i32,
-    pub contents: i32,
+    pub contents: harvest::CropLoad,
 }
 
 #[derive(Component)]
 pub struct Truck {
     pub vehicle: Vehicle,
+    // how fast the tipper rises, in degrees per second
     pub dump_speed: f32,
+    // how far up it is so far
+    pub tipped: f32,
 }
 
 #[derive(Component)]
 pub struct TruckStorage {
     pub capacity: i32,
-    pub contents: i32,
+    pub contents: harvest::CropLoad,
+}
+
+// both vehicles' grain, for the systems that fill or empty the two together
+#[derive(SystemParam)]
+pub struct GrainStores<'w, 's> {
+    pub combine: ResMut<'w, CombineStorage>,
+    pub truck: ResMut<'w, TruckStorage>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+// the models picked in the dealership, what's been bought for them in the shop, and the
+// header last fitted
+#[derive(SystemParam)]
+pub struct Loadout<'w, 's> {
+    career: Res<'w, Career>,
+    catalogue: Res<'w, VehicleCatalogue>,
+    choice: Res<'w, VehicleChoice>,
+    header: Res<'w, header::HeaderChoice>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> Loadout<'w, 's> {
+    pub fn models(&self, level: Level) -> (&VehicleModel, &VehicleModel) {
+        // the tutorial's instructions are written for the standard machines
+        if level == Level::Tutorial {
+            (
+                self.catalogue.model(VehicleClass::Combine, None),
+                self.catalogue.model(VehicleClass::Truck, None),
+            )
+        } else {
+            (
+                self.catalogue
+                    .model(VehicleClass::Combine, self.choice.combine),
+                self.catalogue.model(VehicleClass::Truck, self.choice.truck),
+            )
+        }
+    }
+
+    fn upgrades(&self, level: Level) -> Upgrades {
+        // only the career keeps what was bought in the shop
+        if level == Level::Career {
+            self.career.upgrades
+        } else {
+            Upgrades::default()
+        }
+    }
 }
 
 pub fn setup(
@@ -43,158 +141,182 @@ pub fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut game: ResMut<game::Game>,
+    stores: GrainStores,
+    loadout: Loadout,
     asset_server: Res<AssetServer>,
 ) {
+    let GrainStores {
+        combine: mut combine_storage,
+        truck: mut truck_storage,
+        ..
+    } = stores;
+    let (combine_model, truck_model) = loadout.models(game.level);
+    let upgrades = loadout.upgrades(game.level);
+    combine_storage.capacity = upgrades.combine_capacity(combine_model.capacity);
+    combine_storage.contents.clear();
+    truck_storage.capacity = upgrades.truck_capacity(truck_model.capacity);
+    truck_storage.contents.clear();
+
+    let mut combine_vehicle = combine_model.vehicle();
+    upgrades.apply_engine(&mut combine_vehicle);
+    let mut truck_vehicle = truck_model.vehicle();
+    upgrades.apply_engine(&mut truck_vehicle);
+    let truck_top_speed = truck_vehicle.drive_speed;
+
+    let header_kind = loadout.header.kind;
     let combine_id = commands
         .spawn_bundle(SceneBundle {
-            scene: asset_server.load("combine.gltf#Scene0"),
+            scene: asset_server.load(&combine_model.scene),
             transform: Transform {
-                translation: Vec3::new(60., 1.6, 0.).into(),
+                translation: Vec3::new(60., 1.6, 0.),
                 ..default()
             },
             ..default()
         })
         .insert(Combine {
             vehicle: Vehicle {
-                drive_speed: 10.,
-                turn_rate: 4.,
-                acceleration: 20.,
-                max_reverse: 5.,
+                turn_penalty: header_kind.turn_penalty(),
+                ..combine_vehicle
             },
-            transfer_speed: 10.,
+            transfer_speed: upgrades.transfer_speed(combine_model.unload_speed),
         })
         .insert(
             CollisionLayers::none()
                 .with_groups(&[game::GameLayer::Combine, game::GameLayer::Vehicle])
                 .with_masks(&[
-                    game::GameLayer::Crop,
                     game::GameLayer::Obstacle,
                     game::GameLayer::Vehicle,
                     game::GameLayer::World,
                 ]),
         )
         .insert(RigidBody::Dynamic)
+        .insert(RotationConstraints::restrict_to_y_only())
         .insert(Velocity::from_linear(Vec3::ZERO).with_angular(AxisAngle::new(Vec3::Y, 0.)))
         .insert(CollisionShape::Cuboid {
-            half_extends: Vec3 {
-                x: 1.5,
-                y: 0.3,
-                z: 2.,
-            },
+            half_extends: combine_model.half_extents,
             border_radius: Some(0.2),
         })
+        .insert(navigation::NavRoute::default())
+        .insert(sound::EngineSound::new(combine_vehicle.drive_speed))
+        .insert(minimap::MinimapIcon {
+            color: Color::YELLOW,
+            size: 8.,
+        })
+        .insert(particles::DustTrail::new(1.5, 2.))
         .id();
 
+    // chaff blows out the back, and the auger swings out to the right
+    let chaff_chute = commands
+        .spawn_bundle(SpatialBundle::from_transform(Transform::from_xyz(
+            0., 1.5, 2.5,
+        )))
+        .insert(particles::Emitter::new(
+            particles::ParticleKind::Chaff,
+            60.,
+            Vec3::new(0., 2., 4.),
+            1.5,
+        ))
+        .insert(particles::ChaffChute {})
+        .id();
+    let auger = commands
+        .spawn_bundle(SpatialBundle::from_transform(Transform::from_xyz(
+            3., 3., -0.5,
+        )))
+        .insert(particles::Emitter::new(
+            particles::ParticleKind::Grain,
+            80.,
+            Vec3::new(4., 2., 0.),
+            0.3,
+        ))
+        .insert(particles::Auger {})
+        .id();
+    commands
+        .entity(combine_id)
+        .push_children(&[chaff_chute, auger]);
+
+    header::spawn_header(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        combine_id,
+        header_kind,
+    );
+    daylight::spawn_headlights(
+        &mut commands,
+        combine_id,
+        combine_model.half_extents.x - 0.3,
+        -combine_model.half_extents.z,
+    );
     game.combine = Some(combine_id);
 
     let truck_id = commands
         .spawn_bundle(SceneBundle {
-            scene: asset_server.load("truck.gltf#Scene0"),
+            scene: asset_server.load(&truck_model.scene),
             transform: Transform {
-                translation: Vec3::new(70., 1.6, 0.).into(),
+                translation: Vec3::new(70., 1.6, 0.),
                 ..default()
             },
             ..default()
         })
         .insert(Truck {
-            vehicle: Vehicle {
-                drive_speed: 15.,
-                turn_rate: 3.,
-                acceleration: 40.,
-                max_reverse: 5.,
-            },
-            dump_speed: 20.,
+            vehicle: truck_vehicle,
+            dump_speed: upgrades.dump_speed(truck_model.unload_speed),
+            tipped: 0.,
         })
+        .insert(animals::Herder {})
         .insert(RigidBody::Dynamic)
+        .insert(RotationConstraints::restrict_to_y_only())
         .insert(Velocity::from_linear(Vec3::ZERO).with_angular(AxisAngle::new(Vec3::Y, 0.)))
         .insert(CollisionShape::Cuboid {
-            half_extends: Vec3 {
-                x: 1.5,
-                y: 0.3,
-                z: 1.,
-            },
+            half_extends: truck_model.half_extents,
             border_radius: None,
         })
         .insert(
             CollisionLayers::none()
                 .with_groups(&[game::GameLayer::Truck, game::GameLayer::Vehicle])
                 .with_masks(&[
-                    game::GameLayer::Crop,
                     game::GameLayer::Obstacle,
                     game::GameLayer::Vehicle,
                     game::GameLayer::World,
                 ]),
         )
         .insert(Collisions::default())
+        .insert(crop_grid::CropCutter {
+            half_size: Vec2::new(truck_model.half_extents.x, truck_model.half_extents.z),
+            offset: 0.,
+            action: crop_grid::PlantState::Squashed,
+            enabled: true,
+        })
+        .insert(navigation::NavRoute::default())
+        .insert(sound::EngineSound::new(truck_top_speed))
+        .insert(minimap::MinimapIcon {
+            color: Color::CYAN,
+            size: 7.,
+        })
+        .insert(particles::DustTrail::new(1.2, 1.5))
         .id();
 
+    daylight::spawn_headlights(
+        &mut commands,
+        truck_id,
+        truck_model.half_extents.x - 0.5,
+        -truck_model.half_extents.z - 0.5,
+    );
     game.truck = Some(truck_id);
 }
 
-pub fn combine_collision_check(
-    mut collisions: EventReader<CollisionEvent>,
-    mut crop_harvested_events: EventWriter<harvest::CropHarvestedEvent>,
-) {
-    for collision in collisions.iter() {
-        match collision {
-            CollisionEvent::Started(data1, data2) => {
-                if data1
-                    .collision_layers()
-                    .contains_group(game::GameLayer::Combine)
-                {
-                    crop_harvested_events.send(harvest::CropHarvestedEvent {
-                        entity: data2.rigid_body_entity(),
-                    });
-                } else if data2
-                    .collision_layers()
-                    .contains_group(game::GameLayer::Combine)
-                {
-                    crop_harvested_events.send(harvest::CropHarvestedEvent {
-                        entity: data1.rigid_body_entity(),
-                    });
-                }
-            }
-            _ => {}
-        }
-    }
-}
-
-pub fn truck_collision_check(
-    mut collisions: EventReader<CollisionEvent>,
-    mut crop_squashed_events: EventWriter<harvest::CropSquashedEvent>,
-) {
-    for collision in collisions.iter() {
-        match collision {
-            CollisionEvent::Started(data1, data2) => {
-                if data1
-                    .collision_layers()
-                    .contains_group(game::GameLayer::Truck)
-                {
-                    crop_squashed_events.send(harvest::CropSquashedEvent {
-                        entity: data2.rigid_body_entity(),
-                    })
-                } else if data2
-                    .collision_layers()
-                    .contains_group(game::GameLayer::Truck)
-                {
-                    crop_squashed_events.send(harvest::CropSquashedEvent {
-                        entity: data1.rigid_body_entity(),
-                    })
-                }
-            }
-            _ => {}
-        }
-    }
-}
-
 pub fn move_combine(
     keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Combine, &mut Transform, &mut Velocity)>,
+    mut query: Query<(&mut Combine, &Transform, &mut Velocity)>,
     time: Res<Time>,
+    autopilot: Res<autopilot::CombineAutopilot>,
 ) {
+    if autopilot.enabled {
+        return;
+    }
     // there's always exactly one but I didn't understand resources when I wrote this
     let mut requested_direction = Vec2::new(0., 0.);
-    let (combine, mut transform, mut velocity) = query.single_mut();
+    let (mut combine, transform, mut velocity) = query.single_mut();
     if keyboard_input.pressed(KeyCode::W) {
         requested_direction.y += 1.;
     }
@@ -209,9 +331,9 @@ pub fn move_combine(
     }
 
     update_vehicle(
-        &combine.vehicle,
+        &mut combine.vehicle,
         requested_direction,
-        &mut transform,
+        transform,
         &mut velocity,
         &time,
     );
@@ -219,10 +341,14 @@ pub fn move_combine(
 
 pub fn move_truck(
     keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Truck, &mut Transform, &mut Velocity)>,
+    mut query: Query<(&mut Truck, &Transform, &mut Velocity)>,
     time: Res<Time>,
+    autopilot: Res<autopilot::TruckAutopilot>,
 ) {
-    let (truck, mut transform, mut velocity) = query.single_mut();
+    if autopilot.enabled {
+        return;
+    }
+    let (mut truck, transform, mut velocity) = query.single_mut();
     let mut requested_direction = Vec2::new(0., 0.);
     if keyboard_input.pressed(KeyCode::Up) {
         requested_direction.y += 1.;
@@ -237,82 +363,156 @@ pub fn move_truck(
         requested_direction.x -= 1.;
     }
     update_vehicle(
-        &truck.vehicle,
+        &mut truck.vehicle,
         requested_direction,
-        &mut transform,
+        transform,
         &mut velocity,
         &time,
     );
 }
 
-fn update_vehicle(
-    vehicle: &Vehicle,
+// bicycle model: the front wheels steer towards the requested direction and the body yaws
+// at speed / wheelbase * tan(steering angle). Everything goes through the Velocity so heron
+// still gets to push the vehicles around.
+pub fn update_vehicle(
+    vehicle: &mut Vehicle,
     requested_direction: Vec2,
-    transform: &mut Transform,
-    mut velocity: &mut Velocity,
+    transform: &Transform,
+    velocity: &mut Velocity,
     time: &Res<Time>,
 ) {
-    let mut velocity_on_plane = Vec2::new(velocity.linear.x, velocity.linear.z);
-    let current_speed = velocity_on_plane.length();
-    let mut speed = current_speed;
-    let max_change_in_speed = vehicle.acceleration * time.delta_seconds();
+    let delta = time.delta_seconds();
+    let forward = Vec3::new(transform.forward().x, 0., transform.forward().z).normalize_or_zero();
+    let right = forward.cross(Vec3::Y);
+    let planar_velocity = Vec3::new(velocity.linear.x, 0., velocity.linear.z);
+    let mut speed = planar_velocity.dot(forward);
+    let mut lateral_speed = planar_velocity.dot(right);
 
-    if (requested_direction.length() == 0.) {
-        if current_speed >= 0. {
-            speed = f32::max((current_speed - max_change_in_speed), 0.);
+    let mut throttle = 0.;
+    let mut target_steering = 0.;
+    if requested_direction.length() > 0. {
+        let forward_on_plane = Vec2::new(forward.x, forward.z);
+        let heading_error = requested_direction.angle_between(forward_on_plane);
+        if heading_error.abs() > 3. * FRAC_PI_4 {
+            // backing up, so it's the rear that needs to swing round and the wheels go the
+            // other way
+            throttle = -1.;
+            let rear_error = requested_direction.angle_between(-forward_on_plane);
+            target_steering = -rear_error * STEERING_GAIN;
         } else {
-            speed = f32::min(current_speed + max_change_in_speed, 0.);
-        }
-    } else {
-        let mut xz_velocity = velocity.linear;
-        xz_velocity.y = 0.;
-
-        let forward_on_plane = Vec2::new(transform.forward().x, transform.forward().z);
-        let mut requested_turn_angle = requested_direction.angle_between(forward_on_plane);
-        let reverse = requested_turn_angle.abs() > (3. * FRAC_PI_4);
-
-        speed += vehicle.acceleration * time.delta_seconds();
-
-        if reverse {
-            speed = -1. * speed;
-            if requested_turn_angle > 0. {
-                requested_turn_angle -= PI;
-            } else {
-                requested_turn_angle += PI;
-            }
+            throttle = 1.;
+            target_steering = heading_error * STEERING_GAIN;
         }
+    }
+    target_steering =
+        target_steering.clamp(-vehicle.max_steering_angle, vehicle.max_steering_angle);
+    let max_steering_change = vehicle.turn_rate / (1. + vehicle.turn_penalty) * delta;
+    vehicle.steering_angle +=
+        (target_steering - vehicle.steering_angle).clamp(-max_steering_change, max_steering_change);
 
-        let max_angle_to_turn = vehicle.turn_rate * time.delta_seconds();
-        let mut angle_to_turn = requested_turn_angle.clamp(-max_angle_to_turn, max_angle_to_turn);
+    if throttle == 0. {
+        let max_change_in_speed = vehicle.acceleration * delta;
+        speed -= speed.clamp(-max_change_in_speed, max_change_in_speed);
+    } else if speed * throttle < 0. {
+        let max_change_in_speed = vehicle.braking * delta;
+        speed -= speed.clamp(-max_change_in_speed, max_change_in_speed);
+    } else {
+        speed += throttle * vehicle.acceleration * delta;
+    }
+    vehicle.drag = (vehicle.drag - DRAG_RECOVERY * delta).max(0.);
+    let top_speed = if vehicle.blocked_ahead {
+        0.
+    } else {
+        vehicle.drive_speed * (1. - WET_SPEED_LOSS * vehicle.wetness) / (1. + vehicle.drag)
+    };
+    speed = speed.clamp(-vehicle.max_reverse, top_speed);
 
-        speed = speed.clamp(-vehicle.max_reverse, vehicle.drive_speed);
+    // tyres only scrub off so much sideways slip each frame
+    let grip = vehicle.lateral_grip * (1. - WET_GRIP_LOSS * vehicle.wetness);
+    lateral_speed *= 1. - (grip * delta).min(1.);
 
-        transform.rotate_y(angle_to_turn);
-    }
-    velocity.linear.x = speed * transform.forward().x;
-    velocity.linear.z = speed * transform.forward().z;
+    let yaw_rate = speed / vehicle.wheelbase * vehicle.steering_angle.tan();
+    let new_velocity = forward * speed + right * lateral_speed;
+    velocity.linear.x = new_velocity.x;
+    velocity.linear.z = new_velocity.z;
+    velocity.angular = AxisAngle::new(Vec3::Y, yaw_rate);
 }
 
 pub fn transfer_harvest(
-    mut combines: Query<(&Combine, &Transform)>,
-    mut trucks: Query<(&Truck, &Transform)>,
+    combines: Query<(&Combine, &Transform)>,
+    trucks: Query<&Transform, With<Truck>>,
     mut combine_storage: ResMut<CombineStorage>,
     mut truck_storage: ResMut<TruckStorage>,
+    mut transfer_events: EventWriter<GrainTransferEvent>,
+    time: Res<Time>,
 ) {
-    if let Ok((combine, combine_transform)) = combines.get_single_mut() {
-        if let Ok((truck, truck_transform)) = trucks.get_single_mut() {
+    if let Ok((combine, combine_transform)) = combines.get_single() {
+        if let Ok(truck_transform) = trucks.get_single() {
             if combine_transform
                 .translation
                 .distance(truck_transform.translation)
-                < 15.
+                < TRANSFER_DISTANCE
             {
-                let to_transfer = i32::min(
-                    (truck_storage.capacity - truck_storage.contents),
-                    combine_storage.contents,
-                );
-                truck_storage.contents += to_transfer;
-                combine_storage.contents -= to_transfer;
+                // at least one unit a frame, so a slow auger still gets there
+                let auger_load = (combine.transfer_speed * time.delta_seconds()).ceil() as i32;
+                let to_transfer = auger_load
+                    .min(truck_storage.capacity - truck_storage.contents.total())
+                    .min(combine_storage.contents.total());
+                let transferred = combine_storage.contents.take(to_transfer);
+                truck_storage.contents.add_load(&transferred);
+                if transferred.total() > 0 {
+                    transfer_events.send(GrainTransferEvent {
+                        amount: transferred.total(),
+                    });
+                }
             }
         }
     }
 }
+
+// console: `fill` tops the combine's hopper up with wheat, `fill corn` with corn
+pub fn console_fill(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let crop = match args {
+        [] => harvest::CropType::Wheat,
+        [name] => {
+            harvest::CropType::from_name(name).ok_or_else(|| format!("{} isn't a crop", name))?
+        }
+        _ => return Err("fill takes at most a crop".to_string()),
+    };
+    let mut storage = world.resource_mut::<CombineStorage>();
+    let space = (storage.capacity - storage.contents.total()).max(0);
+    storage.contents.add(crop, space);
+    Ok(format!("added {} {}", space, crop.info().name))
+}
+
+// console: `teleport truck 10 -20` puts the truck down at x 10, z -20
+pub fn console_teleport(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let (vehicle, x, z) = match args {
+        [vehicle, x, z] => match (x.parse::<f32>(), z.parse::<f32>()) {
+            (Ok(x), Ok(z)) => (*vehicle, x, z),
+            _ => return Err("the position must be numbers".to_string()),
+        },
+        _ => return Err("teleport needs combine or truck, then x and z".to_string()),
+    };
+    let entity = match vehicle {
+        "combine" => world
+            .query_filtered::<Entity, With<Combine>>()
+            .iter(world)
+            .next(),
+        "truck" => world
+            .query_filtered::<Entity, With<Truck>>()
+            .iter(world)
+            .next(),
+        _ => return Err(format!("{} isn't combine or truck", vehicle)),
+    }
+    .ok_or_else(|| format!("there's no {} out", vehicle))?;
+    let mut entity = world.entity_mut(entity);
+    if let Some(mut transform) = entity.get_mut::<Transform>() {
+        transform.translation.x = x;
+        transform.translation.z = z;
+    }
+    if let Some(mut velocity) = entity.get_mut::<Velocity>() {
+        *velocity = Velocity::from_linear(Vec3::ZERO).with_angular(AxisAngle::new(Vec3::Y, 0.));
+    }
+    Ok(format!("{} moved to {} {}", vehicle, x, z))
+}
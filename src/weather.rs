@@ -0,0 +1,259 @@
+use crate::game;
+use crate::herding::Dog;
+use crate::rng::{GameRng, RngStream};
+use crate::vehicles::{Combine, Truck};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WeatherKind {
+    Clear,
+    Overcast,
+    Rain,
+    Storm,
+}
+
+pub const WEATHER_KINDS: [WeatherKind; 4] = [
+    WeatherKind::Clear,
+    WeatherKind::Overcast,
+    WeatherKind::Rain,
+    WeatherKind::Storm,
+];
+
+impl WeatherKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WeatherKind::Clear => "Clear",
+            WeatherKind::Overcast => "Overcast",
+            WeatherKind::Rain => "Rain",
+            WeatherKind::Storm => "Storm",
+        }
+    }
+
+    // matches `name`, in any case
+    pub fn from_name(name: &str) -> Option<WeatherKind> {
+        WEATHER_KINDS
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    // multiplies the sunlight
+    fn light_factor(&self) -> f32 {
+        match self {
+            WeatherKind::Clear => 1.,
+            WeatherKind::Overcast => 0.55,
+            WeatherKind::Rain => 0.35,
+            WeatherKind::Storm => 0.2,
+        }
+    }
+
+    // drops per second
+    fn rain_rate(&self) -> f32 {
+        match self {
+            WeatherKind::Clear | WeatherKind::Overcast => 0.,
+            WeatherKind::Rain => 250.,
+            WeatherKind::Storm => 700.,
+        }
+    }
+
+    // how quickly the ground soaks up (or dries out when negative), per second
+    fn soak_rate(&self) -> f32 {
+        match self {
+            WeatherKind::Clear => -0.02,
+            WeatherKind::Overcast => -0.005,
+            WeatherKind::Rain => 0.03,
+            WeatherKind::Storm => 0.08,
+        }
+    }
+
+    // weather only moves one step at a time, so storms always come with a warning
+    fn next(&self, roll: f32) -> WeatherKind {
+        match self {
+            WeatherKind::Clear => WeatherKind::Overcast,
+            WeatherKind::Overcast => {
+                if roll < 0.4 {
+                    WeatherKind::Clear
+                } else {
+                    WeatherKind::Rain
+                }
+            }
+            WeatherKind::Rain => {
+                if roll < 0.6 {
+                    WeatherKind::Overcast
+                } else {
+                    WeatherKind::Storm
+                }
+            }
+            WeatherKind::Storm => WeatherKind::Rain,
+        }
+    }
+}
+
+pub struct Weather {
+    pub current: WeatherKind,
+    // what the forecast says comes after the current weather
+    pub next: WeatherKind,
+    // seconds until `next` arrives
+    pub time_to_change: f32,
+    // 0 is bone dry, 1 is waterlogged
+    pub wetness: f32,
+    light_factor: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Weather {
+            current: WeatherKind::Clear,
+            next: WeatherKind::Overcast,
+            time_to_change: MAX_SPELL,
+            wetness: 0.,
+            light_factor: 1.,
+        }
+    }
+}
+
+impl Weather {
+    // multiplies whatever the sun would otherwise give
+    pub fn light_factor(&self) -> f32 {
+        self.light_factor
+    }
+
+    // the crop is too wet to thresh while it's pouring or the field is waterlogged
+    pub fn crop_wet(&self) -> bool {
+        self.current == WeatherKind::Storm
+            || (self.current == WeatherKind::Rain && self.wetness > WET_CROP_THRESHOLD)
+    }
+}
+
+// seconds each spell of weather lasts
+const MIN_SPELL: f32 = 40.;
+const MAX_SPELL: f32 = 80.;
+const WET_CROP_THRESHOLD: f32 = 0.6;
+// per second
+const LIGHT_CHANGE_RATE: f32 = 0.2;
+
+const RAIN_HEIGHT: f32 = 60.;
+const RAIN_SPEED: f32 = 40.;
+const RAIN_HALF_WIDTH: f32 = 120.;
+const MAX_RAIN_DROPS: usize = 1500;
+
+#[derive(Component)]
+pub struct RainDrop {}
+
+#[derive(Default)]
+pub struct RainAssets {
+    mesh: Option<Handle<Mesh>>,
+    material: Option<Handle<StandardMaterial>>,
+}
+
+pub fn setup(mut weather: ResMut<Weather>) {
+    *weather = Weather::default();
+}
+
+pub fn update_weather(
+    mut weather: ResMut<Weather>,
+    mut rng: ResMut<GameRng>,
+    game: Res<game::Game>,
+    time: Res<Time>,
+) {
+    if !game.level.has_weather() {
+        return;
+    }
+    let delta = time.delta_seconds();
+    weather.time_to_change -= delta;
+    if weather.time_to_change <= 0. {
+        let rng = rng.stream(RngStream::Weather);
+        weather.current = weather.next;
+        weather.next = weather.current.next(rng.gen());
+        weather.time_to_change = rng.gen_range(MIN_SPELL..MAX_SPELL);
+    }
+
+    weather.wetness = (weather.wetness + weather.current.soak_rate() * delta).clamp(0., 1.);
+
+    let target_light = weather.current.light_factor();
+    let max_change = LIGHT_CHANGE_RATE * delta;
+    weather.light_factor += (target_light - weather.light_factor).clamp(-max_change, max_change);
+}
+
+// wet ground is slippery and boggy for everything with wheels (or paws)
+pub fn soak_vehicles(
+    weather: Res<Weather>,
+    mut combines: Query<&mut Combine>,
+    mut trucks: Query<&mut Truck>,
+    mut dogs: Query<&mut Dog>,
+) {
+    for mut combine in combines.iter_mut() {
+        combine.vehicle.wetness = weather.wetness;
+    }
+    for mut truck in trucks.iter_mut() {
+        truck.vehicle.wetness = weather.wetness;
+    }
+    for mut dog in dogs.iter_mut() {
+        dog.vehicle.wetness = weather.wetness;
+    }
+}
+
+pub fn update_rain(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    time: Res<Time>,
+    mut rain_assets: ResMut<RainAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut drops: Query<(Entity, &mut Transform), With<RainDrop>>,
+) {
+    let delta = time.delta_seconds();
+    let mut drop_count = 0;
+    for (entity, mut transform) in drops.iter_mut() {
+        transform.translation.y -= RAIN_SPEED * delta;
+        if transform.translation.y < 0. {
+            commands.entity(entity).despawn();
+        } else {
+            drop_count += 1;
+        }
+    }
+
+    // the drops are only for show, so they don't need to come from GameRng
+    let mut rng = rand::thread_rng();
+    let expected = weather.current.rain_rate() * delta;
+    // carry the fractional part over as a chance of one more drop
+    let mut to_spawn = expected.floor() as usize;
+    if rng.gen::<f32>() < expected.fract() {
+        to_spawn += 1;
+    }
+    to_spawn = to_spawn.min(MAX_RAIN_DROPS.saturating_sub(drop_count));
+    if to_spawn == 0 {
+        return;
+    }
+
+    let mesh = rain_assets
+        .mesh
+        .get_or_insert_with(|| meshes.add(Mesh::from(shape::Box::new(0.05, 1.2, 0.05))))
+        .clone();
+    let material = rain_assets
+        .material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                base_color: Color::rgba(0.7, 0.8, 1., 0.5),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })
+        })
+        .clone();
+    for _ in 0..to_spawn {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(
+                    rng.gen_range(-RAIN_HALF_WIDTH..RAIN_HALF_WIDTH),
+                    rng.gen_range(RAIN_HEIGHT / 2.0..RAIN_HEIGHT),
+                    rng.gen_range(-RAIN_HALF_WIDTH..RAIN_HALF_WIDTH),
+                ),
+                ..default()
+            })
+            .insert(RainDrop {});
+    }
+}